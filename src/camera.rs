@@ -4,13 +4,43 @@
 use bevy::{
     input::mouse::{MouseMotion, MouseWheel},
     prelude::*,
+    render::camera::ScalingMode,
 };
 use bevy_mod_picking::*;
 
+use crate::{caterpillar::CaterpillarHead, dynamic_config::DynamicConfig, ui::SelectedCaterpillar};
+
 #[derive(Resource, Default)]
 pub struct FollowCamera {
     pub enabled: bool,
+    /// Where the camera wants to focus, just ahead of the followed caterpillar.
     pub translation: Vec3,
+    /// Direction the followed caterpillar is heading.
+    pub forward: Vec3,
+    pub target: Option<Entity>,
+    /// The caterpillar the focus has settled on. Differs from `target` when the selection changes.
+    focused: Option<Entity>,
+    retarget: Option<FocusTransition>,
+}
+
+/// Eases the focus from where it was to a newly followed caterpillar.
+struct FocusTransition {
+    from: Vec3,
+    elapsed: f32,
+}
+
+/// Eases `t` from 0 to 1 with a smoothstep curve.
+fn smoothstep(t: f32) -> f32 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Fraction of the remaining distance to cover this frame when damping at the given rate.
+fn damping_factor(damping: f32, delta_seconds: f32) -> f32 {
+    if damping <= 0.0 {
+        1.0
+    } else {
+        1.0 - (-damping * delta_seconds).exp()
+    }
 }
 
 /// Tags an entity as capable of panning and orbiting.
@@ -33,15 +63,25 @@ impl Default for PanOrbitCamera {
 }
 
 /// Pan the camera with middle mouse click, zoom with scroll wheel, orbit with right mouse click.
+#[allow(clippy::too_many_arguments)]
 pub fn pan_orbit_camera(
+    time: Res<Time>,
     windows: Res<Windows>,
+    config: Res<DynamicConfig>,
     mut ev_motion: EventReader<MouseMotion>,
     mut ev_scroll: EventReader<MouseWheel>,
     input_mouse: Res<Input<MouseButton>>,
     keys: Res<Input<KeyCode>>,
-    follow_camera: Res<FollowCamera>,
+    mut follow_camera: ResMut<FollowCamera>,
+    mode_state: Res<CameraModeState>,
     mut query: Query<(&mut PanOrbitCamera, &mut Transform, &Projection)>,
 ) {
+    if !mode_state.orbiting() {
+        ev_motion.clear();
+        ev_scroll.clear();
+        return;
+    }
+
     // change input mapping for orbit and panning here
     let orbit_button = MouseButton::Right;
     let pan_button = MouseButton::Left;
@@ -127,10 +167,47 @@ pub fn pan_orbit_camera(
             pan_orbit.radius = f32::min(pan_orbit.radius, 2500.0);
         }
 
-        let mut focus = pan_orbit.focus;
+        // the focus tracks the followed caterpillar so the camera stays put when it is released.
         if follow_camera.enabled {
-            focus = follow_camera.translation;
+            if follow_camera.focused != follow_camera.target {
+                follow_camera.focused = follow_camera.target;
+                follow_camera.retarget = Some(FocusTransition {
+                    from: pan_orbit.focus,
+                    elapsed: 0.0,
+                });
+            }
+
+            let target = follow_camera.translation;
+            if let Some(retarget) = &mut follow_camera.retarget {
+                retarget.elapsed += time.delta_seconds();
+                let t = (retarget.elapsed / config.camera.retarget_seconds).min(1.0);
+                pan_orbit.focus = retarget.from.lerp(target, smoothstep(t));
+                if t >= 1.0 {
+                    follow_camera.retarget = None;
+                }
+            } else {
+                let damping = damping_factor(config.camera.follow_damping, time.delta_seconds());
+                pan_orbit.focus = pan_orbit.focus.lerp(target, damping);
+            }
+
+            // swing round behind the caterpillar, unless the player is orbiting by hand.
+            if config.camera.auto_orbit && rotation_move.length_squared() == 0.0 {
+                let (yaw, pitch, _) = transform.rotation.to_euler(EulerRot::YXZ);
+                let forward = follow_camera.forward;
+                let target_yaw = f32::atan2(-forward.x, -forward.z);
+                let mut turn = target_yaw - yaw;
+                // take the short way round.
+                turn = (turn + std::f32::consts::PI).rem_euclid(std::f32::consts::TAU)
+                    - std::f32::consts::PI;
+                let turn =
+                    turn * damping_factor(config.camera.auto_orbit_speed, time.delta_seconds());
+                transform.rotation = Quat::from_euler(EulerRot::YXZ, yaw + turn, pitch, 0.0);
+            }
+        } else {
+            follow_camera.focused = None;
+            follow_camera.retarget = None;
         }
+        let focus = pan_orbit.focus;
 
         // emulating parent/child to make the yaw/y-axis rotation behave like a turntable
         // parent = x and y rotation
@@ -146,6 +223,22 @@ pub fn get_primary_window_size(windows: &Res<Windows>) -> Vec2 {
     Vec2::new(window.width() as f32, window.height() as f32)
 }
 
+/// Where a point in the world appears on screen, in pixels from the top left like ui nodes.
+/// None if it is behind the camera or off the edge of the screen.
+pub fn world_to_ui(
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+    position: Vec3,
+) -> Option<Vec2> {
+    let size = camera.logical_viewport_size()?;
+    let viewport = camera.world_to_viewport(camera_transform, position)?;
+    if viewport.x < 0.0 || viewport.y < 0.0 || viewport.x > size.x || viewport.y > size.y {
+        return None;
+    }
+    // the viewport is measured from the bottom left.
+    Some(Vec2::new(viewport.x, size.y - viewport.y))
+}
+
 /// Spawn a camera like this
 pub fn spawn_camera(mut commands: Commands) {
     let translation = Vec3::new(-12.0, 12.5, 5.0);
@@ -168,3 +261,290 @@ pub fn spawn_camera(mut commands: Commands) {
         .insert(pan_orbit)
         .insert(PickingCameraBundle::default());
 }
+
+/// Height the top-down camera sits at. The orthographic projection means this only affects
+/// the transition and what is clipped.
+const TOP_DOWN_HEIGHT: f32 = 800.0;
+
+/// Seconds taken to move the camera between modes.
+const TRANSITION_SECONDS: f32 = 1.0;
+
+const FREE_FLY_SPEED: f32 = 150.0;
+const FREE_FLY_LOOK_SPEED: f32 = 0.005;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum CameraMode {
+    /// The pan and orbit camera.
+    #[default]
+    Orbit,
+    /// Moved with WASD or the arrow keys, looked around with the right mouse button.
+    FreeFly,
+    /// Orthographic overview of the whole world.
+    TopDown,
+    /// From the selected caterpillar's head.
+    FirstPerson,
+    /// Behind and above the selected caterpillar.
+    OverTheShoulder,
+}
+
+/// Eases the camera from where it was to where the new mode puts it. This isn't a bevy_tweening
+/// `Tween`, as those run between end points fixed when they start, and the caterpillar views
+/// keep moving while the camera catches up with them.
+struct CameraTransition {
+    from: Transform,
+    elapsed: f32,
+}
+
+#[derive(Resource, Default)]
+pub struct CameraModeState {
+    pub mode: CameraMode,
+    /// Orbit rotation to go back to when leaving another mode.
+    orbit_rotation: Option<Quat>,
+    transition: Option<CameraTransition>,
+}
+
+impl CameraModeState {
+    /// Whether the pan and orbit camera is in control.
+    pub fn orbiting(&self) -> bool {
+        self.mode == CameraMode::Orbit && self.transition.is_none()
+    }
+}
+
+/// 1 orbit, 2 free-fly, 3 top-down, 4 first-person, 5 over the shoulder.
+pub fn camera_mode_input_system(
+    keys: Res<Input<KeyCode>>,
+    mut mode_state: ResMut<CameraModeState>,
+    selected_query: Query<&SelectedCaterpillar>,
+    mut camera_query: Query<(&Transform, &mut Projection), With<PanOrbitCamera>>,
+) {
+    let modes = [
+        (KeyCode::Key1, CameraMode::Orbit),
+        (KeyCode::Key2, CameraMode::FreeFly),
+        (KeyCode::Key3, CameraMode::TopDown),
+        (KeyCode::Key4, CameraMode::FirstPerson),
+        (KeyCode::Key5, CameraMode::OverTheShoulder),
+    ];
+    let Some(&(_, mode)) = modes.iter().find(|(key, _)| keys.just_pressed(*key)) else {
+        return;
+    };
+    if mode == mode_state.mode {
+        return;
+    }
+    if matches!(mode, CameraMode::FirstPerson | CameraMode::OverTheShoulder)
+        && selected_query.is_empty()
+    {
+        info!("Select a caterpillar to view from it.");
+        return;
+    }
+
+    for (transform, mut projection) in camera_query.iter_mut() {
+        if mode_state.mode == CameraMode::Orbit {
+            mode_state.orbit_rotation = Some(transform.rotation);
+        }
+        if let Projection::Orthographic(_) = *projection {
+            *projection = Projection::Perspective(PerspectiveProjection::default());
+        }
+        // free-fly starts from wherever the camera is.
+        if mode != CameraMode::FreeFly {
+            mode_state.transition = Some(CameraTransition {
+                from: *transform,
+                elapsed: 0.0,
+            });
+        }
+    }
+    mode_state.mode = mode;
+}
+
+/// Flies the camera around. WASD is left to the player while a caterpillar is being steered by
+/// hand, leaving the camera on the arrow keys.
+pub fn free_fly_camera_system(
+    time: Res<Time>,
+    keys: Res<Input<KeyCode>>,
+    input_mouse: Res<Input<MouseButton>>,
+    mut ev_motion: EventReader<MouseMotion>,
+    mode_state: Res<CameraModeState>,
+    caterpillar_query: Query<&CaterpillarHead>,
+    mut camera_query: Query<&mut Transform, With<PanOrbitCamera>>,
+) {
+    if mode_state.mode != CameraMode::FreeFly {
+        ev_motion.clear();
+        return;
+    }
+
+    let mut look = Vec2::ZERO;
+    if input_mouse.pressed(MouseButton::Right) {
+        for ev in ev_motion.iter() {
+            look += ev.delta;
+        }
+    } else {
+        ev_motion.clear();
+    }
+
+    let steering = caterpillar_query
+        .iter()
+        .any(|caterpillar| caterpillar.manually_controlled);
+    let pressed = |arrow: KeyCode, letter: KeyCode| {
+        keys.pressed(arrow) || (!steering && keys.pressed(letter))
+    };
+
+    for mut transform in camera_query.iter_mut() {
+        let mut movement = Vec3::ZERO;
+        if pressed(KeyCode::Up, KeyCode::W) {
+            movement += transform.forward();
+        }
+        if pressed(KeyCode::Down, KeyCode::S) {
+            movement += transform.back();
+        }
+        if pressed(KeyCode::Left, KeyCode::A) {
+            movement += transform.left();
+        }
+        if pressed(KeyCode::Right, KeyCode::D) {
+            movement += transform.right();
+        }
+        if keys.pressed(KeyCode::E) {
+            movement += Vec3::Y;
+        }
+        if keys.pressed(KeyCode::Q) {
+            movement -= Vec3::Y;
+        }
+        let mut speed = FREE_FLY_SPEED;
+        if keys.any_pressed([KeyCode::LShift, KeyCode::RShift]) {
+            speed *= 4.0;
+        }
+        transform.translation += movement.normalize_or_zero() * speed * time.delta_seconds();
+        // stay above the ground.
+        transform.translation.y = f32::max(transform.translation.y, 2.0);
+
+        if look.length_squared() > 0.0 {
+            let yaw = Quat::from_rotation_y(-look.x * FREE_FLY_LOOK_SPEED);
+            let pitch = Quat::from_rotation_x(-look.y * FREE_FLY_LOOK_SPEED);
+            transform.rotation = yaw * transform.rotation * pitch;
+        }
+    }
+}
+
+/// Looking out from a caterpillar's head. The view is levelled with the head's own up rather than
+/// the world's, as a caterpillar climbing a tree faces straight up or down the trunk.
+fn first_person_view(head: &Transform) -> Transform {
+    Transform::from_translation(head.translation + Vec3::Y * 2.5)
+        .looking_at(head.translation + Vec3::Y * 2.5 + head.forward(), head.up())
+}
+
+/// Behind and above a caterpillar's head, looking past it.
+fn over_the_shoulder_view(head: &Transform) -> Transform {
+    Transform::from_translation(head.translation + head.back() * 18.0 + Vec3::Y * 8.0)
+        .looking_at(head.translation + head.forward() * 20.0, head.up())
+}
+
+/// Places the camera for the top-down and caterpillar views, and eases between modes.
+pub fn camera_mode_system(
+    time: Res<Time>,
+    config: Res<DynamicConfig>,
+    mut mode_state: ResMut<CameraModeState>,
+    selected_query: Query<&Transform, (With<SelectedCaterpillar>, Without<PanOrbitCamera>)>,
+    mut camera_query: Query<(&PanOrbitCamera, &mut Transform, &mut Projection)>,
+) {
+    let selected_transform = selected_query.get_single().ok();
+    // the caterpillar being viewed from was deselected or died, so go back to orbiting.
+    let lost_caterpillar = selected_transform.is_none()
+        && matches!(
+            mode_state.mode,
+            CameraMode::FirstPerson | CameraMode::OverTheShoulder
+        );
+
+    for (pan_orbit, mut transform, mut projection) in camera_query.iter_mut() {
+        if lost_caterpillar {
+            mode_state.mode = CameraMode::Orbit;
+            mode_state.transition = Some(CameraTransition {
+                from: *transform,
+                elapsed: 0.0,
+            });
+        }
+
+        let target = match mode_state.mode {
+            CameraMode::Orbit => {
+                if mode_state.transition.is_none() {
+                    return;
+                }
+                let rotation = mode_state.orbit_rotation.unwrap_or(transform.rotation);
+                Transform {
+                    translation: pan_orbit.focus
+                        + Mat3::from_quat(rotation).mul_vec3(Vec3::new(0.0, 0.0, pan_orbit.radius)),
+                    rotation,
+                    ..default()
+                }
+            }
+            CameraMode::FreeFly => return,
+            CameraMode::TopDown => {
+                Transform::from_xyz(0.0, TOP_DOWN_HEIGHT, 0.0).looking_at(Vec3::ZERO, Vec3::NEG_Z)
+            }
+            CameraMode::FirstPerson => first_person_view(selected_transform.unwrap()),
+            CameraMode::OverTheShoulder => over_the_shoulder_view(selected_transform.unwrap()),
+        };
+
+        if let Some(transition) = &mut mode_state.transition {
+            transition.elapsed += time.delta_seconds();
+            let t = (transition.elapsed / TRANSITION_SECONDS).min(1.0);
+            let eased = smoothstep(t);
+            transform.translation = transition.from.translation.lerp(target.translation, eased);
+            transform.rotation = transition.from.rotation.slerp(target.rotation, eased);
+            if t < 1.0 {
+                continue;
+            }
+            mode_state.transition = None;
+        }
+
+        *transform = target;
+        if mode_state.mode == CameraMode::TopDown {
+            if let Projection::Perspective(_) = *projection {
+                *projection = Projection::Orthographic(OrthographicProjection {
+                    far: TOP_DOWN_HEIGHT * 2.0,
+                    scaling_mode: ScalingMode::FixedVertical(config.plane_size),
+                    ..default()
+                });
+            }
+        }
+    }
+}
+
+#[test]
+#[cfg(test)]
+fn first_person_view_test_1() {
+    // a head climbing up a trunk faces straight up.
+    let head = Transform::from_xyz(10.0, 5.0, -3.0).with_rotation(
+        Quat::from_rotation_y(0.7) * Quat::from_rotation_x(std::f32::consts::FRAC_PI_2),
+    );
+
+    let view = first_person_view(&head);
+
+    // Check resulting changes
+    assert!(view.rotation.is_finite());
+    assert!(view.forward().abs_diff_eq(Vec3::Y, 0.001));
+}
+
+#[test]
+#[cfg(test)]
+fn over_the_shoulder_view_test_1() {
+    // a head climbing down a trunk faces straight down.
+    let head = Transform::from_xyz(10.0, 5.0, -3.0).with_rotation(
+        Quat::from_rotation_y(0.7) * Quat::from_rotation_x(-std::f32::consts::FRAC_PI_2),
+    );
+
+    let view = over_the_shoulder_view(&head);
+
+    // Check resulting changes
+    assert!(view.rotation.is_finite());
+    assert!(view.translation.is_finite());
+}
+
+#[test]
+#[cfg(test)]
+fn first_person_view_test_2() {
+    let head = Transform::from_xyz(10.0, 1.0, -3.0).with_rotation(Quat::from_rotation_y(0.7));
+
+    let view = first_person_view(&head);
+
+    // Check resulting changes
+    assert!(view.forward().abs_diff_eq(head.forward(), 0.001));
+    assert!(view.up().abs_diff_eq(Vec3::Y, 0.001));
+}
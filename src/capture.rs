@@ -0,0 +1,466 @@
+use std::{
+    collections::VecDeque,
+    num::NonZeroU32,
+    sync::{Arc, Mutex},
+};
+
+use bevy::{
+    prelude::*,
+    render::{
+        camera::RenderTarget,
+        extract_resource::{ExtractResource, ExtractResourcePlugin},
+        main_graph::node::CAMERA_DRIVER,
+        render_asset::RenderAssets,
+        render_graph::{self, NodeRunError, RenderGraph, RenderGraphContext},
+        render_resource::{
+            Buffer, BufferDescriptor, BufferUsages, Extent3d, ImageCopyBuffer, ImageDataLayout,
+            MapMode, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+        },
+        renderer::{RenderContext, RenderDevice},
+        texture::{BevyDefault, TextureFormatPixelInfo},
+        RenderApp, RenderStage,
+    },
+};
+
+use crate::{
+    camera::PanOrbitCamera, dynamic_config::DynamicConfig, simulation::SimulationTime,
+    toast::ToastEvent,
+};
+
+/// Rows copied out of a texture have to be padded to a multiple of this many bytes.
+const COPY_BYTES_PER_ROW_ALIGNMENT: u32 = 256;
+
+/// Shortest gap between timelapse frames, in simulated seconds.
+const MIN_TIMELAPSE_INTERVAL: f32 = 0.1;
+
+const CAPTURE_NODE: &str = "capture";
+
+/// Tags the camera that renders a copy of the view into an image so it can be saved.
+#[derive(Component)]
+pub struct CaptureCamera;
+
+/// The file to save this frame as, if any. Copied into the render world each frame it changes.
+#[derive(Resource, Clone, Default, ExtractResource)]
+pub struct CaptureFrame {
+    pub name: Option<String>,
+    pub image: Handle<Image>,
+}
+
+/// Frames read back from the GPU, waiting to be saved. Shared by the main and render worlds.
+#[derive(Resource, Clone, Default)]
+pub struct CapturedFrames(Arc<Mutex<Vec<CapturedFrame>>>);
+
+pub struct CapturedFrame {
+    name: String,
+    width: u32,
+    height: u32,
+    padded_bytes_per_row: u32,
+    format: TextureFormat,
+    data: Vec<u8>,
+}
+
+struct Timelapse {
+    timer: Timer,
+    directory: String,
+    frame: u32,
+}
+
+#[derive(Resource, Default)]
+pub struct CaptureState {
+    /// Files still to be captured, one is taken each frame.
+    queue: VecDeque<String>,
+    timelapse: Option<Timelapse>,
+}
+
+/// A copy of the capture image on its way back from the GPU.
+#[derive(Resource)]
+struct PendingCapture {
+    name: String,
+    buffer: Buffer,
+    width: u32,
+    height: u32,
+    padded_bytes_per_row: u32,
+    format: TextureFormat,
+}
+
+/// Copies the capture camera's image back from the GPU whenever a frame is requested.
+pub struct CapturePlugin;
+
+impl Plugin for CapturePlugin {
+    fn build(&self, app: &mut App) {
+        let captured_frames = CapturedFrames::default();
+        app.insert_resource(captured_frames.clone())
+            .init_resource::<CaptureFrame>()
+            .add_plugin(ExtractResourcePlugin::<CaptureFrame>::default());
+
+        let Ok(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app
+            .insert_resource(captured_frames)
+            .add_system_to_stage(RenderStage::Prepare, prepare_capture_buffer)
+            .add_system_to_stage(RenderStage::Cleanup, map_capture_buffer);
+
+        let mut graph = render_app.world.resource_mut::<RenderGraph>();
+        graph.add_node(CAPTURE_NODE, CaptureNode);
+        graph.add_node_edge(CAMERA_DRIVER, CAPTURE_NODE).unwrap();
+    }
+}
+
+fn padded_bytes_per_row(width: u32, format: TextureFormat) -> u32 {
+    let bytes_per_row = width * format.pixel_size() as u32;
+    let padding = (COPY_BYTES_PER_ROW_ALIGNMENT - bytes_per_row % COPY_BYTES_PER_ROW_ALIGNMENT)
+        % COPY_BYTES_PER_ROW_ALIGNMENT;
+    bytes_per_row + padding
+}
+
+fn prepare_capture_buffer(
+    mut commands: Commands,
+    capture_frame: Res<CaptureFrame>,
+    images: Res<RenderAssets<Image>>,
+    render_device: Res<RenderDevice>,
+) {
+    let Some(name) = &capture_frame.name else {
+        return;
+    };
+    let Some(gpu_image) = images.get(&capture_frame.image) else {
+        return;
+    };
+
+    let width = gpu_image.size.x as u32;
+    let height = gpu_image.size.y as u32;
+    let padded_bytes_per_row = padded_bytes_per_row(width, gpu_image.texture_format);
+    let buffer = render_device.create_buffer(&BufferDescriptor {
+        label: Some("capture_buffer"),
+        size: (padded_bytes_per_row * height) as u64,
+        usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    commands.insert_resource(PendingCapture {
+        name: name.clone(),
+        buffer,
+        width,
+        height,
+        padded_bytes_per_row,
+        format: gpu_image.texture_format,
+    });
+}
+
+/// Render graph node copying the capture image into the pending buffer, after every camera
+/// has drawn.
+struct CaptureNode;
+
+impl render_graph::Node for CaptureNode {
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let Some(pending) = world.get_resource::<PendingCapture>() else {
+            return Ok(());
+        };
+        let capture_frame = world.resource::<CaptureFrame>();
+        let Some(gpu_image) = world
+            .resource::<RenderAssets<Image>>()
+            .get(&capture_frame.image)
+        else {
+            return Ok(());
+        };
+
+        render_context.command_encoder.copy_texture_to_buffer(
+            gpu_image.texture.as_image_copy(),
+            ImageCopyBuffer {
+                buffer: &pending.buffer,
+                layout: ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(pending.padded_bytes_per_row),
+                    rows_per_image: None,
+                },
+            },
+            Extent3d {
+                width: pending.width,
+                height: pending.height,
+                depth_or_array_layers: 1,
+            },
+        );
+        Ok(())
+    }
+}
+
+/// Reads the copied frame back once the GPU has finished with it.
+fn map_capture_buffer(
+    mut commands: Commands,
+    pending: Option<Res<PendingCapture>>,
+    captured_frames: Res<CapturedFrames>,
+    render_device: Res<RenderDevice>,
+) {
+    let Some(pending) = pending else {
+        return;
+    };
+    commands.remove_resource::<PendingCapture>();
+
+    let buffer = pending.buffer.clone();
+    let captured_frames = captured_frames.clone();
+    let mut frame = CapturedFrame {
+        name: pending.name.clone(),
+        width: pending.width,
+        height: pending.height,
+        padded_bytes_per_row: pending.padded_bytes_per_row,
+        format: pending.format,
+        data: Vec::new(),
+    };
+    render_device.map_buffer(&pending.buffer.slice(..), MapMode::Read, move |result| {
+        if let Err(e) = result {
+            error!("Could not read back {}: {}", frame.name, e);
+            return;
+        }
+        frame.data = buffer.slice(..).get_mapped_range().to_vec();
+        buffer.unmap();
+        captured_frames.0.lock().unwrap().push(frame);
+    });
+}
+
+fn capture_size(windows: &Windows) -> Extent3d {
+    let window = windows.get_primary().unwrap();
+    Extent3d {
+        width: window.physical_width().max(1),
+        height: window.physical_height().max(1),
+        ..default()
+    }
+}
+
+pub fn setup_capture(
+    mut commands: Commands,
+    windows: Res<Windows>,
+    mut images: ResMut<Assets<Image>>,
+    mut capture_frame: ResMut<CaptureFrame>,
+) {
+    let size = capture_size(&windows);
+    let mut image = Image {
+        texture_descriptor: TextureDescriptor {
+            label: Some("capture_image"),
+            size,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::bevy_default(),
+            mip_level_count: 1,
+            sample_count: 1,
+            usage: TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_DST
+                | TextureUsages::COPY_SRC
+                | TextureUsages::RENDER_ATTACHMENT,
+        },
+        ..default()
+    };
+    image.resize(size);
+    let image_handle = images.add(image);
+
+    commands.insert_resource(CaptureState::default());
+    capture_frame.image = image_handle.clone();
+
+    // only switched on for the frames being captured.
+    commands
+        .spawn(Camera3dBundle {
+            camera: Camera {
+                target: RenderTarget::Image(image_handle),
+                is_active: false,
+                priority: -1,
+                ..default()
+            },
+            ..default()
+        })
+        .insert(CaptureCamera);
+}
+
+/// Milliseconds since the Unix epoch, used to give saved files unique names.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn timestamp() -> u128 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_millis())
+        .unwrap_or_default()
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn timestamp() -> u128 {
+    js_sys::Date::now() as u128
+}
+
+/// F12 saves a screenshot. F10 starts and stops a timelapse, which saves a frame every
+/// `capture.timelapseInterval` simulated seconds.
+pub fn capture_request_system(
+    keys: Res<Input<KeyCode>>,
+    config: Res<DynamicConfig>,
+    simulation_time: Res<SimulationTime>,
+    mut capture_state: ResMut<CaptureState>,
+    mut ev_toast: EventWriter<ToastEvent>,
+) {
+    let directory = &config.capture.output_directory;
+
+    if keys.just_pressed(KeyCode::F12) {
+        capture_state
+            .queue
+            .push_back(format!("{}/screenshot-{}.png", directory, timestamp()));
+    }
+
+    if keys.just_pressed(KeyCode::F10) {
+        let message = if let Some(timelapse) = capture_state.timelapse.take() {
+            format!("Timelapse stopped after {} frames.", timelapse.frame)
+        } else {
+            let interval = config
+                .capture
+                .timelapse_interval
+                .max(MIN_TIMELAPSE_INTERVAL);
+            capture_state.timelapse = Some(Timelapse {
+                timer: Timer::from_seconds(interval, TimerMode::Repeating),
+                directory: format!("{}/timelapse-{}", directory, timestamp()),
+                frame: 0,
+            });
+            format!("Timelapse started, a frame every {}s.", interval)
+        };
+        ev_toast.send(ToastEvent {
+            message,
+            expiry_tick: 3000,
+        });
+    }
+
+    let mut timelapse_frame = None;
+    if let Some(timelapse) = &mut capture_state.timelapse {
+        if timelapse
+            .timer
+            .tick(simulation_time.delta())
+            .just_finished()
+        {
+            timelapse_frame = Some(format!(
+                "{}/frame-{:05}.png",
+                timelapse.directory, timelapse.frame
+            ));
+            timelapse.frame += 1;
+        }
+    }
+    if let Some(name) = timelapse_frame {
+        capture_state.queue.push_back(name);
+    }
+}
+
+/// Switches the capture camera on, matching the main camera, for the next queued frame.
+#[allow(clippy::type_complexity)]
+pub fn capture_system(
+    windows: Res<Windows>,
+    capture_state: Option<ResMut<CaptureState>>,
+    mut capture_frame: ResMut<CaptureFrame>,
+    mut images: ResMut<Assets<Image>>,
+    view_query: Query<(&Transform, &Projection), (With<PanOrbitCamera>, Without<CaptureCamera>)>,
+    mut capture_camera_query: Query<
+        (&mut Camera, &mut Transform, &mut Projection),
+        With<CaptureCamera>,
+    >,
+) {
+    let next = capture_state.and_then(|mut capture_state| capture_state.queue.pop_front());
+    // leave the resource alone when idle, it is only sent to the render world when it changes.
+    if next.is_none() && capture_frame.name.is_none() {
+        return;
+    }
+
+    for (mut camera, mut transform, mut projection) in capture_camera_query.iter_mut() {
+        camera.is_active = next.is_some();
+        if let Ok((view_transform, view_projection)) = view_query.get_single() {
+            *transform = *view_transform;
+            *projection = view_projection.clone();
+        }
+    }
+
+    // keep the image the same size as the window. `get_mut` reuploads the image, so check first.
+    let size = capture_size(&windows);
+    let resize = images
+        .get(&capture_frame.image)
+        .map_or(false, |image| image.texture_descriptor.size != size);
+    if next.is_some() && resize {
+        if let Some(image) = images.get_mut(&capture_frame.image) {
+            image.resize(size);
+        }
+    }
+
+    capture_frame.name = next;
+}
+
+fn frame_to_image(frame: &CapturedFrame) -> Option<image::RgbaImage> {
+    let bytes_per_row = (frame.width * 4) as usize;
+    let mut pixels = Vec::with_capacity(bytes_per_row * frame.height as usize);
+    for row in frame
+        .data
+        .chunks(frame.padded_bytes_per_row as usize)
+        .take(frame.height as usize)
+    {
+        pixels.extend_from_slice(&row[..bytes_per_row]);
+    }
+    if matches!(
+        frame.format,
+        TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb
+    ) {
+        for pixel in pixels.chunks_exact_mut(4) {
+            pixel.swap(0, 2);
+        }
+    }
+    image::RgbaImage::from_raw(frame.width, frame.height, pixels)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn save_png(path: &str, image: &image::RgbaImage) {
+    if let Some(directory) = std::path::Path::new(path).parent() {
+        if let Err(e) = std::fs::create_dir_all(directory) {
+            error!("Could not create {}: {}", directory.display(), e);
+            return;
+        }
+    }
+    match image.save(path) {
+        Ok(()) => info!("Saved {}.", path),
+        Err(e) => error!("Could not save {}: {}", path, e),
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn save_png(path: &str, image: &image::RgbaImage) {
+    // browsers can't write files, so the png is offered as a download instead.
+    let mut png = std::io::Cursor::new(Vec::new());
+    if let Err(e) = image.write_to(&mut png, image::ImageOutputFormat::Png) {
+        error!("Could not encode {}: {}", path, e);
+        return;
+    }
+    let file_name = path.rsplit('/').next().unwrap_or(path);
+    match download(file_name, png.get_ref()) {
+        Ok(()) => info!("Downloaded {}.", file_name),
+        Err(e) => error!("Could not download {}: {:?}", file_name, e),
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn download(file_name: &str, bytes: &[u8]) -> Result<(), wasm_bindgen::JsValue> {
+    use wasm_bindgen::JsCast;
+
+    let parts = js_sys::Array::of1(&js_sys::Uint8Array::from(bytes));
+    let mut options = web_sys::BlobPropertyBag::new();
+    options.type_("image/png");
+    let blob = web_sys::Blob::new_with_u8_array_sequence_and_options(&parts, &options)?;
+    let url = web_sys::Url::create_object_url_with_blob(&blob)?;
+
+    let document = web_sys::window()
+        .and_then(|window| window.document())
+        .ok_or("No document to download from.")?;
+    let anchor: web_sys::HtmlAnchorElement = document.create_element("a")?.dyn_into()?;
+    anchor.set_href(&url);
+    anchor.set_download(file_name);
+    anchor.click();
+    web_sys::Url::revoke_object_url(&url)
+}
+
+/// Writes out frames that have come back from the GPU.
+pub fn save_captures_system(captured_frames: Res<CapturedFrames>) {
+    let frames = std::mem::take(&mut *captured_frames.0.lock().unwrap());
+    for frame in frames {
+        match frame_to_image(&frame) {
+            Some(image) => save_png(&frame.name, &image),
+            None => error!("Captured frame {} was the wrong size.", frame.name),
+        }
+    }
+}
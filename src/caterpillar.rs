@@ -1,8 +1,22 @@
 use std::time::Duration;
 
 use crate::{
-    camera::FollowCamera, collision, dynamic_config::DynamicConfig, foliage::Food, random,
-    toast::ToastEvent, ui::SelectedCaterpillar,
+    camera::FollowCamera,
+    climbing::Climbing,
+    collision,
+    day_night::Sleeping,
+    dynamic_config::{DynamicConfig, GameMode, PaletteConfig},
+    foliage::{Food, FoodEatenEvent},
+    game::PlayerCaterpillar,
+    lod::LodMeshes,
+    predator::Fleeing,
+    random,
+    simulation::SimulationTime,
+    social::Following,
+    thoughts::{Situation, FIRST_THOUGHT_SECONDS},
+    toast::ToastEvent,
+    ui::SelectedCaterpillar,
+    weather::Weather,
 };
 use bevy::prelude::*;
 use bevy_mod_picking::*;
@@ -19,36 +33,145 @@ pub struct CaterpillarHead {
     pub speed: f32,
     pub next: Option<Entity>,
     pub manually_controlled: bool,
-    pub frames: i32,
+    /** Seconds of simulated time until the caterpillar picks a new direction. */
+    pub wander_timer: f32,
     pub name: String,
     pub description: String,
+    /** What the caterpillar is thinking about, which decides its thoughts. */
+    pub situation: Situation,
+    /** Seconds of simulated time until the caterpillar has a new thought. */
+    pub thought_timer: f32,
+    pub food_eaten: i32,
+    /** Simulation time the caterpillar last took a bite. */
+    pub last_meal: f32,
+    /** The food type last eaten, or none for a tree's canopy. */
+    pub last_food: Option<usize>,
+    /** Seconds of simulated time until the caterpillar can take another bite. */
+    pub bite_cooldown: f32,
+    /** Total goodness gained from food, after the caterpillar's tastes are taken into account. */
+    pub nourishment: f32,
+    /** How much the caterpillar likes each food type, by index into the configured foods. */
+    pub diet: Vec<f32>,
+    pub distance_travelled: f32,
+    /** Simulation time the caterpillar was spawned at. */
+    pub born: f32,
+    /** Index of the palette the caterpillar is painted in. */
+    pub palette: usize,
 
     pub angle: f32,
     pub angle_offset: f32,
     pub angle_offset_direction: AngleOffsetDirection,
 }
 
+impl CaterpillarHead {
+    /// How much the caterpillar likes a food type, 1 being an ordinary liking.
+    pub fn preference(&self, kind: usize) -> f32 {
+        self.diet.get(kind).copied().unwrap_or(1.0)
+    }
+
+    /// The food type the caterpillar likes best.
+    pub fn favourite_food(&self) -> Option<usize> {
+        self.diet
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map(|(kind, _)| kind)
+    }
+}
+
+/// The `angle` a caterpillar at `from` needs to head towards `to`.
+pub fn heading_to(from: Vec3, to: Vec3) -> f32 {
+    let offset = to - from;
+    // caterpillars move along their local -Z axis.
+    f32::atan2(-offset.x, -offset.z)
+}
+
+/// Gives a caterpillar its own tastes, liking each food type a random amount either side of 1.
+pub fn random_diet(config: &DynamicConfig) -> Vec<f32> {
+    let variation = config.diet.variation.max(0.0);
+    config
+        .foods
+        .iter()
+        .map(|_| random::range_f32(1.0 - variation, 1.0 + variation).max(0.0))
+        .collect()
+}
+
+/// Picks the palette a caterpillar is painted in: the one `appearance.byName` gives for its name,
+/// or else one at random.
+pub fn pick_palette(config: &DynamicConfig, name: &str) -> usize {
+    let palettes = &config.appearance.palettes;
+    let named = config
+        .appearance
+        .by_name
+        .get(name)
+        .and_then(|palette_name| palettes.iter().position(|p| p.name == *palette_name));
+    match named {
+        Some(palette) => palette,
+        None if palettes.is_empty() => 0,
+        None => random::range_i32(0, palettes.len() as i32) as usize,
+    }
+}
+
 #[derive(Component)]
 pub struct CaterpillarPart {
     pub next: Option<Entity>,
+    /** The head this part belongs to. */
+    pub head: Entity,
 }
 
+/// Holds the material a segment had before it was highlighted.
+#[derive(Component)]
+pub struct Highlighted(pub Handle<StandardMaterial>);
+
+#[allow(clippy::type_complexity)]
 pub fn caterpillar_system(
     keyboard_input: Res<Input<KeyCode>>,
-    time: Res<Time>,
+    time: Res<SimulationTime>,
     dynamic_config: Res<DynamicConfig>,
-    mut query: Query<(&mut Transform, &mut CaterpillarHead), Without<CaterpillarPart>>,
+    weather: Res<Weather>,
+    mut query: Query<
+        (
+            &mut Transform,
+            &mut CaterpillarHead,
+            Option<&Sleeping>,
+            Option<&Climbing>,
+            Option<&Fleeing>,
+            Option<&Following>,
+        ),
+        Without<CaterpillarPart>,
+    >,
     mut part_query: Query<(&mut Transform, &mut CaterpillarPart), Without<CaterpillarHead>>,
 ) {
-    for (mut transform, mut caterpillar) in query.iter_mut() {
+    for (mut transform, mut caterpillar, sleeping, climbing, fleeing, following) in query.iter_mut()
+    {
+        if sleeping.is_some() {
+            continue;
+        }
         let direction;
+        let mut speed = caterpillar.speed * weather.speed_multiplier(&dynamic_config);
+        if fleeing.is_some() {
+            speed *= dynamic_config.predators.flee_speed_multiplier;
+        }
+        // a procession breaks up while its members climb or run.
+        if let Some(following) = following.filter(|_| climbing.is_none() && fleeing.is_none()) {
+            speed *= following.pace;
+        }
 
-        if caterpillar.manually_controlled {
+        if let Some(climbing) = climbing {
+            // climbing caterpillars face straight up or down the trunk.
+            if !climbing.is_moving() {
+                continue;
+            }
+            direction = transform.forward();
+            speed *= dynamic_config.trees.climb_speed;
+        } else if caterpillar.manually_controlled {
+            let turn = dynamic_config.game.player_turn_speed * time.delta_seconds();
             if keyboard_input.pressed(KeyCode::A) {
-                transform.rotate(Quat::from_rotation_y(0.2));
+                caterpillar.angle += turn;
             } else if keyboard_input.pressed(KeyCode::D) {
-                transform.rotate(Quat::from_rotation_y(-0.2));
+                caterpillar.angle -= turn;
             }
+            transform.rotation = Quat::from_rotation_y(caterpillar.angle);
 
             if keyboard_input.pressed(KeyCode::W) {
                 direction = transform.forward();
@@ -58,100 +181,239 @@ pub fn caterpillar_system(
                 continue;
             }
         } else {
-            if caterpillar.frames == 0 {
+            if caterpillar.wander_timer <= 0.0 {
                 caterpillar.angle_offset = 0.0;
                 let mut angle = rand::random();
                 angle *= 2.0 * std::f32::consts::PI;
                 caterpillar.angle = angle;
                 transform.rotate(Quat::from_rotation_y(angle));
 
-                caterpillar.frames = random::range_i32(48000, 50000);
-
-                caterpillar.description = random::from_vec(&dynamic_config.thoughts);
+                caterpillar.wander_timer = random::range_f32(800.0, 833.0);
             }
             direction = transform.forward();
-            caterpillar.frames -= 1;
-        }
+            caterpillar.wander_timer -= time.delta_seconds();
 
-        const ANGLE_MAX: f32 = 1.6;
-        const ANGLE_CHANGE: f32 = 0.001;
-        if caterpillar.angle_offset.abs() > ANGLE_MAX {
+            const ANGLE_MAX: f32 = 1.6;
+            const ANGLE_CHANGE: f32 = 0.001;
+            // the wiggle was tuned per frame at 60fps.
+            let frame_scale = time.delta_seconds() * 60.0;
+            if caterpillar.angle_offset.abs() > ANGLE_MAX {
+                if caterpillar.angle_offset_direction == AngleOffsetDirection::Left {
+                    caterpillar.angle_offset_direction = AngleOffsetDirection::Right;
+                } else {
+                    caterpillar.angle_offset_direction = AngleOffsetDirection::Left;
+                }
+            }
+            let dyn_angle_change = ((1.0
+                - (caterpillar.angle_offset.abs() - (ANGLE_MAX / 2.0))
+                    .abs()
+                    .asin())
+                * 0.01
+                + ANGLE_CHANGE)
+                * frame_scale;
             if caterpillar.angle_offset_direction == AngleOffsetDirection::Left {
-                caterpillar.angle_offset_direction = AngleOffsetDirection::Right;
+                caterpillar.angle_offset = caterpillar.angle_offset - dyn_angle_change;
             } else {
-                caterpillar.angle_offset_direction = AngleOffsetDirection::Left;
+                caterpillar.angle_offset = caterpillar.angle_offset + dyn_angle_change;
             }
-        }
-        let dyn_angle_change = (1.0
-            - (caterpillar.angle_offset.abs() - (ANGLE_MAX / 2.0))
-                .abs()
-                .asin())
-            * 0.01
-            + ANGLE_CHANGE;
-        if caterpillar.angle_offset_direction == AngleOffsetDirection::Left {
-            caterpillar.angle_offset = caterpillar.angle_offset - dyn_angle_change;
-        } else {
-            caterpillar.angle_offset = caterpillar.angle_offset + dyn_angle_change;
-        }
 
-        transform.rotation = Quat::from_rotation_y(caterpillar.angle + caterpillar.angle_offset);
+            transform.rotation =
+                Quat::from_rotation_y(caterpillar.angle + caterpillar.angle_offset);
+        }
 
-        transform.translation += direction * caterpillar.speed * time.delta_seconds();
+        let step = speed * time.delta_seconds();
+        transform.translation += direction * step;
+        caterpillar.distance_travelled += step;
 
         let mut caterpillar_part = caterpillar.next;
         let mut parent_transform = transform.translation;
 
         while let Some(x) = caterpillar_part {
             if let Ok((mut part_transform, part)) = part_query.get_mut(x) {
-                part_transform.look_at(parent_transform, Vec3::Y);
+                // Y can't be up while following a caterpillar straight up or down a trunk.
+                let to_parent = (parent_transform - part_transform.translation).normalize_or_zero();
+                let up = if to_parent.dot(Vec3::Y).abs() > 0.99 {
+                    Vec3::Z
+                } else {
+                    Vec3::Y
+                };
+                part_transform.look_at(parent_transform, up);
 
                 let fwd = part_transform.forward();
 
                 let distance = Vec3::distance(parent_transform, part_transform.translation);
                 if distance > 3.0 {
-                    part_transform.translation += fwd * caterpillar.speed * time.delta_seconds();
+                    // at high simulation speeds a full step would carry the part past its parent.
+                    let part_step = (speed * time.delta_seconds()).min(distance - 3.0);
+                    part_transform.translation += fwd * part_step;
                 }
 
                 parent_transform = part_transform.translation;
                 caterpillar_part = part.next;
+            } else {
+                break;
             }
         }
     }
 }
 
+/// Caterpillars touching a plant they like take a bite out of it, finishing it off once it's all
+/// gone. How much they gain depends on the food and how much they like it.
+#[allow(clippy::type_complexity)]
 pub fn eat_check(
     mut commands: Commands,
-    caterpillar_query: Query<(&mut Transform, &mut CaterpillarHead), Without<Food>>,
-    food_query: Query<(Entity, &mut Transform, &mut Food)>,
+    time: Res<SimulationTime>,
+    config: Res<DynamicConfig>,
+    mut caterpillar_query: Query<
+        (&Transform, &mut CaterpillarHead),
+        (Without<Food>, Without<Climbing>),
+    >,
+    mut food_query: Query<(Entity, &mut Transform, &mut Food)>,
     mut ev_toast: EventWriter<ToastEvent>,
+    mut ev_eaten: EventWriter<FoodEatenEvent>,
 ) {
-    for caterpillar in caterpillar_query.iter() {
-        for food in food_query.iter() {
-            if collision::collision_check(caterpillar.0.translation, food.1.translation, 4.0) {
-                info!("{}: YUM YUM!!!", caterpillar.1.name);
+    for (transform, mut caterpillar) in caterpillar_query.iter_mut() {
+        caterpillar.bite_cooldown = (caterpillar.bite_cooldown - time.delta_seconds()).max(0.0);
+        if caterpillar.bite_cooldown > 0.0 {
+            continue;
+        }
+
+        for (food_entity, mut food_transform, mut food) in food_query.iter_mut() {
+            // finished off earlier this frame, waiting to be despawned.
+            if food.amount <= 0.0 {
+                continue;
+            }
+            let preference = caterpillar.preference(food.kind);
+            if preference < config.diet.refuse_below {
+                continue;
+            }
+            if collision::collision_check(
+                transform.translation,
+                food_transform.translation,
+                EAT_REACH,
+            ) {
+                let food_type = &config.foods[food.kind];
+                info!("{}: YUM YUM!!!", caterpillar.name);
                 ev_toast.send(ToastEvent {
-                    message: format!("{}: YUM YUM!!!", caterpillar.1.name),
+                    message: format!("{}: YUM YUM!!! ({})", caterpillar.name, food_type.name),
                     expiry_tick: 5000,
                 });
-                commands.entity(food.0).despawn();
+                ev_eaten.send(FoodEatenEvent {
+                    position: food_transform.translation,
+                    food: Some(food_entity),
+                });
+                let bite = config.bushes.bite_size.min(food.amount);
+                caterpillar.food_eaten += 1;
+                caterpillar.last_meal = time.elapsed_seconds();
+                caterpillar.last_food = Some(food.kind);
+                caterpillar.nourishment += bite * food_type.nutrition * preference;
+                caterpillar.bite_cooldown = config.bushes.bite_interval;
+
+                food.amount -= bite;
+                if food.amount <= 0.0 {
+                    commands.entity(food_entity).despawn();
+                } else {
+                    food.size_transform(&mut food_transform, food_type);
+                }
+                break;
             }
         }
     }
 }
 
-pub fn setup_caterpillars(
+#[derive(Resource)]
+pub struct CaterpillarAssets {
+    pub head_meshes: LodMeshes,
+    pub eye_sphere_handle: Handle<Mesh>,
+    pub nose_sphere_handle: Handle<Mesh>,
+    pub body_meshes: LodMeshes,
+    pub foot_sphere_handle: Handle<Mesh>,
+    pub spot_sphere_handle: Handle<Mesh>,
+    pub hair_handle: Handle<Mesh>,
+
+    pub eye_material_handle: Handle<StandardMaterial>,
+    pub highlight_material_handle: Handle<StandardMaterial>,
+    /** One set of materials for each configured palette, in the same order. */
+    pub palettes: Vec<PaletteMaterials>,
+    /** Segments in each stripe. */
+    pub stripe_width: i32,
+}
+
+/// Materials for one of the configured palettes. Markings the config leaves out are `None`.
+pub struct PaletteMaterials {
+    pub head: Handle<StandardMaterial>,
+    pub nose: Handle<StandardMaterial>,
+    pub body: Handle<StandardMaterial>,
+    pub stripe: Option<Handle<StandardMaterial>>,
+    pub feet: Handle<StandardMaterial>,
+    pub spots: Option<Handle<StandardMaterial>>,
+    pub hair: Option<Handle<StandardMaterial>>,
+}
+
+impl CaterpillarAssets {
+    /// The materials for a palette, or the first palette if there is no such one.
+    pub fn palette(&self, palette: usize) -> &PaletteMaterials {
+        self.palettes.get(palette).unwrap_or(&self.palettes[0])
+    }
+
+    /// The material for one of a caterpillar's segments, counting from 0 for the head. Segments
+    /// behind the head alternate between stripes of body and stripe colour.
+    pub fn segment_material(&self, palette: usize, segment: usize) -> Handle<StandardMaterial> {
+        let palette = self.palette(palette);
+        if segment == 0 {
+            return palette.head.clone();
+        }
+        let stripe_width = self.stripe_width.max(1) as usize;
+        match &palette.stripe {
+            Some(stripe) if (segment - 1) / stripe_width % 2 == 1 => stripe.clone(),
+            _ => palette.body.clone(),
+        }
+    }
+}
+
+/// Everything needed to spawn a caterpillar.
+pub struct CaterpillarSpawn {
+    pub name: String,
+    pub description: String,
+    pub speed: f32,
+    /** Number of segments, including the head. */
+    pub length: i32,
+    pub translation: Vec3,
+    pub manually_controlled: bool,
+    /** How much the caterpillar likes each food type. */
+    pub diet: Vec<f32>,
+    /** Index of the palette the caterpillar is painted in. */
+    pub palette: usize,
+}
+
+/// How close a caterpillar's head has to be to a bush to eat from it.
+pub const EAT_REACH: f32 = 4.0;
+
+const HEAD_RADIUS: f32 = 2.0;
+const BODY_RADIUS: f32 = 1.5;
+
+pub fn setup_caterpillar_assets(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
     config: Res<DynamicConfig>,
 ) {
-    let head_radius = 2.0;
-    let body_radius = 1.5;
-    let head_sphere_handle = meshes.add(Mesh::from(shape::UVSphere {
-        radius: head_radius,
-        sectors: 16,
-        stacks: 16,
-    }));
+    let technique = config.rendering.technique;
+    let head_meshes = LodMeshes::new(
+        &mut meshes,
+        technique,
+        Mesh::from(shape::UVSphere {
+            radius: HEAD_RADIUS,
+            sectors: 16,
+            stacks: 16,
+        }),
+        Mesh::from(shape::UVSphere {
+            radius: HEAD_RADIUS,
+            sectors: 8,
+            stacks: 6,
+        }),
+        None,
+    );
     let eye_sphere_handle = meshes.add(Mesh::from(shape::UVSphere {
         radius: 0.25,
         sectors: 16,
@@ -162,173 +424,394 @@ pub fn setup_caterpillars(
         sectors: 16,
         stacks: 16,
     }));
-    let sphere_handle = meshes.add(Mesh::from(shape::UVSphere {
-        radius: body_radius,
-        sectors: 16,
-        stacks: 16,
-    }));
+    // segments turn to follow the caterpillar, so they can't be billboards.
+    let body_meshes = LodMeshes::new(
+        &mut meshes,
+        technique,
+        Mesh::from(shape::UVSphere {
+            radius: BODY_RADIUS,
+            sectors: 16,
+            stacks: 16,
+        }),
+        Mesh::from(shape::UVSphere {
+            radius: BODY_RADIUS,
+            sectors: 8,
+            stacks: 6,
+        }),
+        None,
+    );
     let foot_sphere_handle = meshes.add(Mesh::from(shape::UVSphere {
         radius: 0.25,
         sectors: 6,
         stacks: 6,
     }));
-    let head_material_handle = materials.add(StandardMaterial {
-        base_color: Color::rgb(1.0, 0.0, 0.0),
-        ..default()
-    });
+    let spot_sphere_handle = meshes.add(Mesh::from(shape::UVSphere {
+        radius: 0.4,
+        sectors: 6,
+        stacks: 6,
+    }));
+    let hair_handle = meshes.add(Mesh::from(shape::Box::new(0.15, 1.2, 0.15)));
 
     let eye_material_handle = materials.add(StandardMaterial {
         base_color: Color::rgb(0.0, 0.0, 0.0),
         ..default()
     });
-    let nose_material_handle = materials.add(StandardMaterial {
-        base_color: Color::rgb(1.0, 0.0, 1.0),
-        ..default()
-    });
-    let sphere_material_handle = materials.add(StandardMaterial {
-        base_color: Color::rgb(0.8, 0.7, 0.6),
+
+    let appearance = &config.appearance;
+    let default_palettes = [PaletteConfig::default()];
+    let palette_configs = if appearance.palettes.is_empty() {
+        &default_palettes[..]
+    } else {
+        &appearance.palettes[..]
+    };
+    let mut material = |colour: [f32; 3]| {
+        materials.add(StandardMaterial {
+            base_color: Color::rgb(colour[0], colour[1], colour[2]),
+            ..default()
+        })
+    };
+    let palettes = palette_configs
+        .iter()
+        .map(|palette| PaletteMaterials {
+            head: material(palette.head),
+            nose: material(palette.nose),
+            body: material(palette.body),
+            stripe: palette
+                .stripe
+                .filter(|_| appearance.stripe_width > 0)
+                .map(&mut material),
+            feet: material(palette.feet),
+            spots: palette
+                .spots
+                .filter(|_| appearance.spots)
+                .map(&mut material),
+            hair: palette.hair.filter(|_| appearance.hair).map(&mut material),
+        })
+        .collect();
+
+    let highlight_material_handle = materials.add(StandardMaterial {
+        base_color: Color::rgb(1.0, 0.9, 0.3),
+        emissive: Color::rgb(0.6, 0.5, 0.1),
         ..default()
     });
-    let foot_sphere_material_handle = materials.add(StandardMaterial {
-        base_color: Color::rgb(0.0, 0.0, 0.0),
-        ..default()
+
+    commands.insert_resource(CaterpillarAssets {
+        head_meshes,
+        eye_sphere_handle,
+        nose_sphere_handle,
+        body_meshes,
+        foot_sphere_handle,
+        spot_sphere_handle,
+        hair_handle,
+        eye_material_handle,
+        highlight_material_handle,
+        palettes,
+        stripe_width: appearance.stripe_width,
     });
+}
+
+pub fn setup_caterpillars(
+    mut commands: Commands,
+    caterpillar_assets: Res<CaterpillarAssets>,
+    config: Res<DynamicConfig>,
+) {
+    for i in 0..config.starting_caterpillars {
+        // in game mode the first caterpillar belongs to the player.
+        let is_player = config.game_mode == GameMode::Game && i == 0;
 
-    for _ in 0..config.starting_caterpillars {
         let mut starting_vec = random::vec3(config.starting_caterpillar_radius);
         starting_vec.y = 3.0;
-        let starting_transform = Transform::default().with_translation(starting_vec);
-
-        let mut part_entity_option: Option<Entity> = Option::None;
-
-        let length =
-            random::range_i32(config.caterpillar_min_length, config.caterpillar_max_length);
-        for _ in 1..length {
-            let caterpillar_part = CaterpillarPart {
-                next: part_entity_option,
-            };
-
-            // leg tween
-            let leg_tween_l = Tween::new(
-                EaseFunction::QuadraticInOut,
-                Duration::from_millis(750),
-                TransformPositionLens {
-                    start: Vec3::new(-3.5, -2.0, -1.5),
-                    end: Vec3::new(-3.5, -2.0, 1.5),
-                },
+
+        let (name, speed) = if is_player {
+            ("You".to_string(), config.game.player_speed)
+        } else {
+            (
+                random::from_vec(&config.names),
+                random::range_f32(config.caterpillar_min_speed, config.caterpillar_max_speed),
             )
-            .with_repeat_strategy(RepeatStrategy::MirroredRepeat)
-            .with_repeat_count(RepeatCount::Infinite);
-
-            // leg tween
-            let leg_tween_r = Tween::new(
-                EaseFunction::QuadraticInOut,
-                Duration::from_secs(1),
-                TransformPositionLens {
-                    start: Vec3::new(3.5, -2.0, -1.5),
-                    end: Vec3::new(3.5, -2.0, 1.5),
+        };
+
+        let palette = pick_palette(&config, &name);
+        let head = spawn_caterpillar(
+            &mut commands,
+            &caterpillar_assets,
+            CaterpillarSpawn {
+                name,
+                description: random::from_vec(&config.thoughts),
+                speed,
+                length: random::range_i32(
+                    config.caterpillar_min_length,
+                    config.caterpillar_max_length,
+                ),
+                translation: starting_vec,
+                manually_controlled: is_player,
+                // the player eats whatever they steer into.
+                diet: if is_player {
+                    vec![1.0; config.foods.len()]
+                } else {
+                    random_diet(&config)
                 },
-            )
-            .with_repeat_strategy(RepeatStrategy::MirroredRepeat)
-            .with_repeat_count(RepeatCount::Infinite);
-
-            let part_entity = commands
-                .spawn(PbrBundle {
-                    mesh: sphere_handle.clone(),
-                    material: sphere_material_handle.clone(),
-                    transform: starting_transform,
-                    ..default()
-                })
-                .insert(caterpillar_part)
-                .with_children(|parent| {
-                    // body spheres
-                    parent
-                        .spawn(PbrBundle {
-                            mesh: foot_sphere_handle.clone(),
-                            material: foot_sphere_material_handle.clone(),
-                            transform: Transform::from_xyz(-3.5, -2.0, 0.0),
+                palette,
+            },
+            0.0,
+        );
+        if is_player {
+            commands
+                .entity(head)
+                .insert(PlayerCaterpillar)
+                .insert(SelectedCaterpillar);
+        }
+    }
+}
+
+/// Spawns a caterpillar and its chain of body parts, returning the head.
+pub fn spawn_caterpillar(
+    commands: &mut Commands,
+    caterpillar_assets: &CaterpillarAssets,
+    spawn: CaterpillarSpawn,
+    born: f32,
+) -> Entity {
+    let starting_transform = Transform::default().with_translation(spawn.translation);
+
+    // the head is reserved up front so every part can point back at it.
+    let head_entity = commands.spawn_empty().id();
+
+    let mut part_entity_option: Option<Entity> = Option::None;
+    let palette = caterpillar_assets.palette(spawn.palette);
+
+    // parts are spawned tail first, as each points at the one behind it.
+    for segment in (1..spawn.length).rev() {
+        let caterpillar_part = CaterpillarPart {
+            next: part_entity_option,
+            head: head_entity,
+        };
+
+        // leg tween
+        let leg_tween_l = Tween::new(
+            EaseFunction::QuadraticInOut,
+            Duration::from_millis(750),
+            TransformPositionLens {
+                start: Vec3::new(-3.5, -2.0, -1.5),
+                end: Vec3::new(-3.5, -2.0, 1.5),
+            },
+        )
+        .with_repeat_strategy(RepeatStrategy::MirroredRepeat)
+        .with_repeat_count(RepeatCount::Infinite);
+
+        // leg tween
+        let leg_tween_r = Tween::new(
+            EaseFunction::QuadraticInOut,
+            Duration::from_secs(1),
+            TransformPositionLens {
+                start: Vec3::new(3.5, -2.0, -1.5),
+                end: Vec3::new(3.5, -2.0, 1.5),
+            },
+        )
+        .with_repeat_strategy(RepeatStrategy::MirroredRepeat)
+        .with_repeat_count(RepeatCount::Infinite);
+
+        let mut part = commands.spawn(PbrBundle {
+            mesh: caterpillar_assets.body_meshes.mesh(),
+            material: caterpillar_assets.segment_material(spawn.palette, segment as usize),
+            transform: starting_transform,
+            ..default()
+        });
+        caterpillar_assets.body_meshes.insert_lod(&mut part);
+        let part_entity = part
+            .insert(caterpillar_part)
+            .insert(PickableBundle::default())
+            .with_children(|parent| {
+                // body spheres
+                parent
+                    .spawn(PbrBundle {
+                        mesh: caterpillar_assets.foot_sphere_handle.clone(),
+                        material: palette.feet.clone(),
+                        transform: Transform::from_xyz(-3.5, -2.0, 0.0),
+                        ..default()
+                    })
+                    .insert(Animator::new(leg_tween_l));
+                parent
+                    .spawn(PbrBundle {
+                        mesh: caterpillar_assets.foot_sphere_handle.clone(),
+                        material: palette.feet.clone(),
+                        transform: Transform::from_xyz(3.5, -2.0, 0.0),
+                        ..default()
+                    })
+                    .insert(Animator::new(leg_tween_r));
+                if let Some(spots) = &palette.spots {
+                    for x in [-0.7, 0.7] {
+                        parent.spawn(PbrBundle {
+                            mesh: caterpillar_assets.spot_sphere_handle.clone(),
+                            material: spots.clone(),
+                            transform: Transform::from_xyz(x, 1.1, 0.0),
                             ..default()
-                        })
-                        .insert(Animator::new(leg_tween_l));
-                    parent
-                        .spawn(PbrBundle {
-                            mesh: foot_sphere_handle.clone(),
-                            material: foot_sphere_material_handle.clone(),
-                            transform: Transform::from_xyz(3.5, -2.0, 0.0),
+                        });
+                    }
+                }
+                if let Some(hair) = &palette.hair {
+                    for tilt in [-0.4, 0.0, 0.4] {
+                        parent.spawn(PbrBundle {
+                            mesh: caterpillar_assets.hair_handle.clone(),
+                            material: hair.clone(),
+                            transform: Transform::from_xyz(0.0, BODY_RADIUS + 0.4, 0.0)
+                                .with_rotation(Quat::from_rotation_z(tilt)),
                             ..default()
-                        })
-                        .insert(Animator::new(leg_tween_r));
-                })
-                .id();
+                        });
+                    }
+                }
+            })
+            .id();
 
-            part_entity_option = Option::from(part_entity);
-        }
+        part_entity_option = Option::from(part_entity);
+    }
 
-        // head sphere
-        commands
-            .spawn(PbrBundle {
-                mesh: head_sphere_handle.clone(),
-                material: head_material_handle.clone(),
-                transform: starting_transform,
+    // head sphere
+    caterpillar_assets
+        .head_meshes
+        .insert_lod(&mut commands.entity(head_entity));
+    commands
+        .entity(head_entity)
+        .insert(PbrBundle {
+            mesh: caterpillar_assets.head_meshes.mesh(),
+            material: caterpillar_assets.segment_material(spawn.palette, 0),
+            transform: starting_transform,
+            ..default()
+        })
+        .insert(PickableBundle::default())
+        .insert(CaterpillarHead {
+            speed: spawn.speed,
+            next: part_entity_option,
+            manually_controlled: spawn.manually_controlled,
+            wander_timer: 0.0,
+            name: spawn.name,
+            description: spawn.description,
+            situation: Situation::Wandering,
+            thought_timer: FIRST_THOUGHT_SECONDS,
+            food_eaten: 0,
+            last_meal: born,
+            last_food: None,
+            bite_cooldown: 0.0,
+            nourishment: 0.0,
+            diet: spawn.diet,
+            distance_travelled: 0.0,
+            born,
+            palette: spawn.palette,
+            angle: 0.0,
+            angle_offset: 0.0,
+            angle_offset_direction: AngleOffsetDirection::Left,
+        })
+        .with_children(|parent| {
+            // nose
+            parent.spawn(PbrBundle {
+                mesh: caterpillar_assets.nose_sphere_handle.clone(),
+                material: palette.nose.clone(),
+                transform: Transform::from_xyz(0.0, 0.0, -HEAD_RADIUS),
                 ..default()
-            })
-            .insert(PickableBundle::default())
-            .insert(CaterpillarHead {
-                speed: random::range_f32(
-                    config.caterpillar_min_speed,
-                    config.caterpillar_max_speed,
-                ),
-                next: part_entity_option,
-                manually_controlled: false,
-                frames: 0,
-                name: random::from_vec(&config.names),
-                description: random::from_vec(&config.thoughts),
-                angle: 0.0,
-                angle_offset: 0.0,
-                angle_offset_direction: AngleOffsetDirection::Left,
-            })
-            .with_children(|parent| {
-                // nose
-                parent.spawn(PbrBundle {
-                    mesh: nose_sphere_handle.clone(),
-                    material: nose_material_handle.clone(),
-                    transform: Transform::from_xyz(0.0, 0.0, -head_radius),
-                    ..default()
-                });
-                let eye_offset_x = (head_radius / 2.0) - 0.3;
-                let eye_offset_y = head_radius / 2.0;
-                // left eye
-                parent.spawn(PbrBundle {
-                    mesh: eye_sphere_handle.clone(),
-                    material: eye_material_handle.clone(),
-                    transform: Transform::from_xyz(
-                        eye_offset_x,
-                        eye_offset_y,
-                        -(head_radius - 0.5),
-                    ),
-                    ..default()
-                });
-                // right eye
-                parent.spawn(PbrBundle {
-                    mesh: eye_sphere_handle.clone(),
-                    material: eye_material_handle.clone(),
-                    transform: Transform::from_xyz(
-                        -eye_offset_x,
-                        eye_offset_y,
-                        -(head_radius - 0.5),
-                    ),
-                    ..default()
-                });
             });
+            let eye_offset_x = (HEAD_RADIUS / 2.0) - 0.3;
+            let eye_offset_y = HEAD_RADIUS / 2.0;
+            // left eye
+            parent.spawn(PbrBundle {
+                mesh: caterpillar_assets.eye_sphere_handle.clone(),
+                material: caterpillar_assets.eye_material_handle.clone(),
+                transform: Transform::from_xyz(eye_offset_x, eye_offset_y, -(HEAD_RADIUS - 0.5)),
+                ..default()
+            });
+            // right eye
+            parent.spawn(PbrBundle {
+                mesh: caterpillar_assets.eye_sphere_handle.clone(),
+                material: caterpillar_assets.eye_material_handle.clone(),
+                transform: Transform::from_xyz(-eye_offset_x, eye_offset_y, -(HEAD_RADIUS - 0.5)),
+                ..default()
+            });
+        })
+        .id()
+}
+
+/// Despawns a caterpillar's head and every part in its chain.
+pub fn despawn_caterpillar(
+    commands: &mut Commands,
+    head_entity: Entity,
+    head: &CaterpillarHead,
+    part_query: &Query<&CaterpillarPart>,
+) {
+    let mut caterpillar_part = head.next;
+    while let Some(part_entity) = caterpillar_part {
+        commands.entity(part_entity).despawn_recursive();
+        caterpillar_part = part_query.get(part_entity).ok().and_then(|part| part.next);
     }
+    commands.entity(head_entity).despawn_recursive();
+}
+
+/// Despawns the last part of a caterpillar, leaving the rest of it behind. Returns false if the
+/// caterpillar is only a head.
+pub fn remove_tail_segment(
+    commands: &mut Commands,
+    head: &mut CaterpillarHead,
+    part_query: &mut Query<&mut CaterpillarPart>,
+) -> bool {
+    let Some(mut tail) = head.next else {
+        return false;
+    };
+    let mut before_tail: Option<Entity> = None;
+    while let Some(next) = part_query.get(tail).ok().and_then(|part| part.next) {
+        before_tail = Some(tail);
+        tail = next;
+    }
+
+    match before_tail {
+        Some(part_entity) => {
+            if let Ok(mut part) = part_query.get_mut(part_entity) {
+                part.next = None;
+            }
+        }
+        None => head.next = None,
+    }
+    commands.entity(tail).despawn_recursive();
+    true
+}
+
+/// Counts the segments of a caterpillar, including the head.
+pub fn caterpillar_length(head: &CaterpillarHead, part_query: &Query<&CaterpillarPart>) -> i32 {
+    let mut length = 1;
+    let mut caterpillar_part = head.next;
+    while let Some(part_entity) = caterpillar_part {
+        length += 1;
+        caterpillar_part = part_query.get(part_entity).ok().and_then(|part| part.next);
+    }
+    length
 }
 
 pub fn camera_follow_caterpillar_system(
+    config: Res<DynamicConfig>,
     mut follow_camera: ResMut<FollowCamera>,
-    selected_caterpillar_query: Query<(&SelectedCaterpillar, &Transform)>,
+    selected_caterpillar_query: Query<
+        (Entity, &CaterpillarHead, &Transform),
+        With<SelectedCaterpillar>,
+    >,
 ) {
-    for (_, transform) in selected_caterpillar_query.iter() {
-        follow_camera.translation = transform.translation;
+    // release the camera where it is when nothing is selected.
+    follow_camera.enabled = false;
+    follow_camera.target = None;
+    for (entity, caterpillar, transform) in selected_caterpillar_query.iter() {
+        // aim a little ahead so the caterpillar isn't always chasing the edge of the screen.
+        follow_camera.translation = transform.translation
+            + transform.forward() * caterpillar.speed * config.camera.look_ahead;
+        follow_camera.forward = transform.forward();
+        follow_camera.target = Some(entity);
         follow_camera.enabled = true;
     }
 }
+
+/// Freezes the leg tweens while the level is paused.
+pub fn pause_leg_animation_system(mut animator_query: Query<&mut Animator<Transform>>) {
+    for mut animator in animator_query.iter_mut() {
+        animator.state = AnimatorState::Paused;
+    }
+}
+
+pub fn resume_leg_animation_system(mut animator_query: Query<&mut Animator<Transform>>) {
+    for mut animator in animator_query.iter_mut() {
+        animator.state = AnimatorState::Playing;
+    }
+}
@@ -0,0 +1,287 @@
+use std::f32::consts::FRAC_PI_2;
+
+use bevy::prelude::*;
+
+use crate::{
+    caterpillar::CaterpillarHead,
+    day_night::{DayNight, Sleeping},
+    dynamic_config::DynamicConfig,
+    foliage::{Canopy, FoodEatenEvent, Tree},
+    simulation::SimulationTime,
+    toast::ToastEvent,
+    weather::Weather,
+};
+
+/// Height of a caterpillar's head when it is on the ground.
+const GROUND_HEIGHT: f32 = 3.0;
+
+/// How close to the middle of a trunk a caterpillar's head has to come to start climbing.
+const TRUNK_REACH: f32 = 4.0;
+
+/// How far from the middle of the trunk a climbing caterpillar clings on.
+const TRUNK_GRIP: f32 = 3.0;
+
+/// Radius of a canopy sphere before it is scaled.
+const CANOPY_RADIUS: f32 = 8.0;
+
+/// A nibbled canopy is still drawn at least this big.
+const MIN_CANOPY_SCALE: f32 = 0.3;
+
+#[derive(PartialEq, Eq)]
+pub enum ClimbPhase {
+    Up,
+    Eating,
+    Down,
+}
+
+/// A caterpillar on its way up a tree, eating in the canopy or on its way back down.
+#[derive(Component)]
+pub struct Climbing {
+    pub tree: Entity,
+    pub phase: ClimbPhase,
+    /// Height the head climbs to, just below the lowest canopy sphere.
+    top: f32,
+    /// Heading pointing away from the trunk, from the side the caterpillar clings to.
+    yaw: f32,
+    bites: u32,
+}
+
+impl Climbing {
+    pub fn is_moving(&self) -> bool {
+        self.phase != ClimbPhase::Eating
+    }
+}
+
+/// Stops a caterpillar that has just climbed down from going straight back up.
+#[derive(Component)]
+pub struct ClimbCooldown(pub f32);
+
+/// Food held by a canopy sphere.
+#[derive(Component)]
+pub struct CanopyFood {
+    pub amount: f32,
+    pub max_amount: f32,
+    /// Scale of the sphere when it is fully grown.
+    rest_scale: f32,
+}
+
+impl CanopyFood {
+    fn size_transform(&self, transform: &mut Transform) {
+        let fraction = if self.max_amount > 0.0 {
+            (self.amount / self.max_amount).clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+        transform.scale =
+            Vec3::splat(self.rest_scale * (MIN_CANOPY_SCALE + (1.0 - MIN_CANOPY_SCALE) * fraction));
+    }
+}
+
+/// Fills new canopy spheres with food. Bigger spheres hold more.
+pub fn setup_canopy_food_system(
+    mut commands: Commands,
+    config: Res<DynamicConfig>,
+    canopy_query: Query<(Entity, &Transform), Added<Canopy>>,
+) {
+    for (entity, transform) in canopy_query.iter() {
+        let rest_scale = transform.scale.x;
+        let max_amount = config.trees.canopy_food * rest_scale;
+        commands.entity(entity).insert(CanopyFood {
+            amount: max_amount,
+            max_amount,
+            rest_scale,
+        });
+    }
+}
+
+/// Sends wandering caterpillars up any tree they bump into that still has food in its canopy.
+#[allow(clippy::type_complexity)]
+pub fn start_climb_system(
+    mut commands: Commands,
+    simulation_time: Res<SimulationTime>,
+    config: Res<DynamicConfig>,
+    mut head_query: Query<
+        (
+            Entity,
+            &mut Transform,
+            &CaterpillarHead,
+            Option<&mut ClimbCooldown>,
+        ),
+        (Without<Climbing>, Without<Sleeping>, Without<Tree>),
+    >,
+    tree_query: Query<(Entity, &Transform, &Children), With<Tree>>,
+    canopy_query: Query<(&Transform, &CanopyFood), Without<CaterpillarHead>>,
+) {
+    for (head_entity, mut transform, caterpillar, cooldown) in head_query.iter_mut() {
+        if let Some(mut cooldown) = cooldown {
+            cooldown.0 -= simulation_time.delta_seconds();
+            if cooldown.0 <= 0.0 {
+                commands.entity(head_entity).remove::<ClimbCooldown>();
+            }
+            continue;
+        }
+        if !config.trees.edible || caterpillar.manually_controlled {
+            continue;
+        }
+
+        for (tree_entity, tree_transform, children) in tree_query.iter() {
+            let mut outward = transform.translation - tree_transform.translation;
+            outward.y = 0.0;
+            if outward.length() > TRUNK_REACH {
+                continue;
+            }
+
+            let canopies: Vec<_> = children
+                .iter()
+                .filter_map(|child| canopy_query.get(*child).ok())
+                .collect();
+            if !canopies
+                .iter()
+                .any(|(_, food)| food.amount >= config.bushes.bite_size)
+            {
+                continue;
+            }
+            let top = canopies
+                .iter()
+                .map(|(canopy_transform, _)| {
+                    tree_transform.translation.y + canopy_transform.translation.y
+                        - CANOPY_RADIUS * canopy_transform.scale.y
+                })
+                .fold(f32::MAX, f32::min)
+                .max(GROUND_HEIGHT + TRUNK_REACH);
+
+            // cling to the side of the trunk the caterpillar arrived from. it faces up the trunk,
+            // and away from it once it climbs back down.
+            let outward = outward.try_normalize().unwrap_or(Vec3::X);
+            let yaw = f32::atan2(-outward.x, -outward.z);
+            transform.translation.x = tree_transform.translation.x + outward.x * TRUNK_GRIP;
+            transform.translation.z = tree_transform.translation.z + outward.z * TRUNK_GRIP;
+            transform.rotation = Quat::from_rotation_y(yaw) * Quat::from_rotation_x(FRAC_PI_2);
+
+            commands.entity(head_entity).insert(Climbing {
+                tree: tree_entity,
+                phase: ClimbPhase::Up,
+                top,
+                yaw,
+                bites: 0,
+            });
+            break;
+        }
+    }
+}
+
+/// Moves climbing caterpillars between climbing up, eating and climbing down. The climbing itself
+/// is done by `caterpillar_system`, which moves heads along the way they face.
+#[allow(clippy::too_many_arguments)]
+pub fn climb_system(
+    mut commands: Commands,
+    simulation_time: Res<SimulationTime>,
+    config: Res<DynamicConfig>,
+    mut head_query: Query<
+        (Entity, &mut Transform, &mut CaterpillarHead, &mut Climbing),
+        Without<Sleeping>,
+    >,
+    tree_query: Query<&Children, With<Tree>>,
+    mut canopy_query: Query<(&mut Transform, &mut CanopyFood), Without<CaterpillarHead>>,
+    mut ev_toast: EventWriter<ToastEvent>,
+    mut ev_eaten: EventWriter<FoodEatenEvent>,
+) {
+    for (head_entity, mut transform, mut caterpillar, mut climbing) in head_query.iter_mut() {
+        match climbing.phase {
+            ClimbPhase::Up => {
+                if transform.translation.y >= climbing.top {
+                    transform.translation.y = climbing.top;
+                    climbing.phase = ClimbPhase::Eating;
+                    ev_toast.send(ToastEvent {
+                        message: format!("{} has climbed a tree.", caterpillar.name),
+                        expiry_tick: 3000,
+                    });
+                }
+            }
+            ClimbPhase::Eating => {
+                caterpillar.bite_cooldown =
+                    (caterpillar.bite_cooldown - simulation_time.delta_seconds()).max(0.0);
+                if caterpillar.bite_cooldown > 0.0 {
+                    continue;
+                }
+
+                let canopy = tree_query.get(climbing.tree).ok().and_then(|children| {
+                    children
+                        .iter()
+                        .find(|child| {
+                            canopy_query
+                                .get(**child)
+                                .map_or(false, |(_, food)| food.amount > 0.0)
+                        })
+                        .copied()
+                });
+                let full = climbing.bites >= config.trees.bites_per_climb;
+                match canopy.filter(|_| !full) {
+                    Some(canopy) => {
+                        let Ok((mut canopy_transform, mut food)) = canopy_query.get_mut(canopy)
+                        else {
+                            continue;
+                        };
+                        let bite = config.bushes.bite_size.min(food.amount);
+                        food.amount -= bite;
+                        food.size_transform(&mut canopy_transform);
+
+                        caterpillar.food_eaten += 1;
+                        caterpillar.last_meal = simulation_time.elapsed_seconds();
+                        caterpillar.last_food = None;
+                        caterpillar.nourishment += bite * config.trees.nutrition;
+                        caterpillar.bite_cooldown = config.bushes.bite_interval;
+                        climbing.bites += 1;
+                        ev_eaten.send(FoodEatenEvent {
+                            position: transform.translation,
+                            food: None,
+                        });
+                    }
+                    None => {
+                        climbing.phase = ClimbPhase::Down;
+                        transform.rotation =
+                            Quat::from_rotation_y(climbing.yaw) * Quat::from_rotation_x(-FRAC_PI_2);
+                    }
+                }
+            }
+            ClimbPhase::Down => {
+                if transform.translation.y <= GROUND_HEIGHT {
+                    // set off away from the tree.
+                    transform.translation.y = GROUND_HEIGHT;
+                    caterpillar.angle = climbing.yaw;
+                    caterpillar.angle_offset = 0.0;
+                    transform.rotation = Quat::from_rotation_y(caterpillar.angle);
+                    commands
+                        .entity(head_entity)
+                        .remove::<Climbing>()
+                        .insert(ClimbCooldown(config.trees.climb_cooldown));
+                }
+            }
+        }
+    }
+}
+
+/// Grows canopies back during the day, faster in the rain.
+pub fn canopy_growth_system(
+    simulation_time: Res<SimulationTime>,
+    config: Res<DynamicConfig>,
+    day_night: Res<DayNight>,
+    weather: Res<Weather>,
+    mut canopy_query: Query<(&mut Transform, &mut CanopyFood)>,
+) {
+    if day_night.is_night() || simulation_time.delta_seconds() == 0.0 {
+        return;
+    }
+
+    let mut growth = config.trees.regrowth_rate * simulation_time.delta_seconds();
+    if weather.raining {
+        growth *= config.weather.rain_growth_multiplier;
+    }
+    for (mut transform, mut food) in canopy_query.iter_mut() {
+        if food.amount >= food.max_amount {
+            continue;
+        }
+        food.amount = (food.amount + growth).min(food.max_amount);
+        food.size_transform(&mut transform);
+    }
+}
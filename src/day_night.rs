@@ -0,0 +1,172 @@
+use std::f32::consts::{PI, TAU};
+
+use bevy::prelude::*;
+
+use crate::{
+    caterpillar::CaterpillarHead, dynamic_config::DynamicConfig, simulation::SimulationTime,
+    toast::ToastEvent,
+};
+
+/// Sky colour at midday, and when there is no day/night cycle.
+pub const DAY_SKY_COLOUR: Color = Color::rgb(0.53, 0.80, 0.92);
+const NIGHT_SKY_COLOUR: Color = Color::rgb(0.02, 0.03, 0.10);
+const SUNSET_SKY_COLOUR: Color = Color::rgb(0.93, 0.55, 0.35);
+
+const NOON_SUN_COLOUR: Color = Color::WHITE;
+const LOW_SUN_COLOUR: Color = Color::rgb(1.0, 0.6, 0.3);
+
+/// Brightness of the sun at midday, the same as Bevy's default directional light.
+const NOON_ILLUMINANCE: f32 = 100000.0;
+
+/// The light takes the part of the moon at night, so the world can still be made out.
+const MOON_ILLUMINANCE: f32 = 8000.0;
+const MOON_COLOUR: Color = Color::rgb(0.6, 0.7, 1.0);
+
+/// Ambient brightness during the day, the same as Bevy's default.
+pub const DAY_AMBIENT_BRIGHTNESS: f32 = 0.05;
+const NIGHT_AMBIENT_BRIGHTNESS: f32 = 0.02;
+
+/// Tags the directional light that plays the sun.
+#[derive(Component)]
+pub struct Sun;
+
+/// A caterpillar asleep for the night.
+#[derive(Component)]
+pub struct Sleeping;
+
+/// Time of day as a fraction, 0 being midnight, 0.25 sunrise, 0.5 noon and 0.75 sunset.
+#[derive(Resource)]
+pub struct DayNight {
+    pub enabled: bool,
+    pub time_of_day: f32,
+    pub day: u32,
+    /// Whether the caterpillars were last sent to sleep rather than woken.
+    was_night: bool,
+}
+
+impl DayNight {
+    /// Height of the sun above the horizon in radians. Negative at night.
+    pub fn sun_elevation(&self) -> f32 {
+        (self.time_of_day - 0.25) * TAU
+    }
+
+    /// How bright the day is, from 0 at night to 1 at noon.
+    pub fn daylight(&self) -> f32 {
+        if self.enabled {
+            self.sun_elevation().sin().max(0.0)
+        } else {
+            1.0
+        }
+    }
+
+    pub fn is_night(&self) -> bool {
+        self.enabled && !(0.25..0.75).contains(&self.time_of_day)
+    }
+
+    /// The time of day as a 24 hour clock.
+    pub fn clock(&self) -> String {
+        let minutes = (self.time_of_day * 24.0 * 60.0) as u32;
+        format!("Day {} {:02}:{:02}", self.day, minutes / 60, minutes % 60)
+    }
+}
+
+pub fn setup_day_night(mut commands: Commands, config: Res<DynamicConfig>) {
+    commands.insert_resource(DayNight {
+        // a day with no length can't pass, so it's treated as having the cycle turned off.
+        enabled: config.day_night.enabled && config.day_night.day_length > 0.0,
+        time_of_day: config.day_night.start_time.rem_euclid(1.0),
+        day: 1,
+        was_night: false,
+    });
+}
+
+fn lerp_colour(from: Color, to: Color, t: f32) -> Color {
+    let t = t.clamp(0.0, 1.0);
+    Color::rgb(
+        from.r() + (to.r() - from.r()) * t,
+        from.g() + (to.g() - from.g()) * t,
+        from.b() + (to.b() - from.b()) * t,
+    )
+}
+
+/// Moves the clock on, and turns the sun, sky and ambient light to match.
+pub fn day_night_system(
+    simulation_time: Res<SimulationTime>,
+    config: Res<DynamicConfig>,
+    mut day_night: ResMut<DayNight>,
+    mut clear_colour: ResMut<ClearColor>,
+    mut ambient_light: ResMut<AmbientLight>,
+    mut sun_query: Query<(&mut DirectionalLight, &mut Transform), With<Sun>>,
+) {
+    if !day_night.enabled {
+        return;
+    }
+
+    day_night.time_of_day += simulation_time.delta_seconds() / config.day_night.day_length;
+    if day_night.time_of_day >= 1.0 {
+        day_night.time_of_day -= 1.0;
+        day_night.day += 1;
+    }
+
+    let elevation = day_night.sun_elevation();
+    let daylight = day_night.daylight();
+    // the sky glows around sunrise and sunset, when the sun is near the horizon.
+    let twilight = (1.0 - elevation.sin().abs() * 4.0).max(0.0);
+
+    for (mut light, mut transform) in sun_query.iter_mut() {
+        // the sun rises in the east, crosses overhead and sets in the west. the moon follows
+        // the same path through the night.
+        let light_elevation = if daylight > 0.0 {
+            light.illuminance = NOON_ILLUMINANCE * daylight;
+            light.color = lerp_colour(LOW_SUN_COLOUR, NOON_SUN_COLOUR, daylight * 2.0);
+            elevation
+        } else {
+            light.illuminance = MOON_ILLUMINANCE * (-elevation.sin()).max(0.0);
+            light.color = MOON_COLOUR;
+            elevation - PI
+        };
+        transform.rotation =
+            Quat::from_rotation_y(-PI / 2.0) * Quat::from_rotation_x(-light_elevation);
+    }
+
+    let sky = lerp_colour(NIGHT_SKY_COLOUR, DAY_SKY_COLOUR, daylight * 3.0);
+    clear_colour.0 = lerp_colour(sky, SUNSET_SKY_COLOUR, twilight * 0.6);
+
+    ambient_light.brightness =
+        NIGHT_AMBIENT_BRIGHTNESS + (DAY_AMBIENT_BRIGHTNESS - NIGHT_AMBIENT_BRIGHTNESS) * daylight;
+    ambient_light.color = lerp_colour(MOON_COLOUR, Color::WHITE, daylight * 3.0);
+}
+
+/// Puts the wandering caterpillars to sleep at nightfall and wakes them at dawn. The player's
+/// caterpillar stays awake.
+pub fn sleep_system(
+    mut commands: Commands,
+    mut day_night: ResMut<DayNight>,
+    caterpillar_query: Query<(Entity, &CaterpillarHead, Option<&Sleeping>)>,
+    mut ev_toast: EventWriter<ToastEvent>,
+) {
+    let is_night = day_night.is_night();
+    if is_night != day_night.was_night {
+        ev_toast.send(ToastEvent {
+            message: if is_night {
+                "Night falls. The caterpillars curl up to sleep.".to_string()
+            } else {
+                format!("{}. The caterpillars wake up.", day_night.clock())
+            },
+            expiry_tick: 5000,
+        });
+        day_night.was_night = is_night;
+    }
+
+    for (entity, caterpillar, sleeping) in caterpillar_query.iter() {
+        match (is_night && !caterpillar.manually_controlled, sleeping) {
+            (true, None) => {
+                commands.entity(entity).insert(Sleeping);
+            }
+            (false, Some(_)) => {
+                commands.entity(entity).remove::<Sleeping>();
+            }
+            _ => {}
+        }
+    }
+}
@@ -1,10 +1,13 @@
-use std::env;
+use std::{collections::HashMap, env};
 
 use bevy::prelude::*;
 
-use crate::AppState;
+use crate::{replay, AppState};
 
-#[derive(serde::Deserialize, bevy::reflect::TypeUuid, Resource)]
+/// Configs bundled in the assets folder, offered by the menu.
+pub const CONFIG_FILES: [&str; 3] = ["data.json", "game.json", "caterpillar.json"];
+
+#[derive(serde::Deserialize, serde::Serialize, bevy::reflect::TypeUuid, Resource, Clone)]
 #[uuid = "3b661374-e6a2-11ec-8fea-0242ac120002"]
 #[serde(rename_all = "camelCase")]
 pub struct DynamicConfig {
@@ -19,6 +22,26 @@ pub struct DynamicConfig {
 
     pub bush_spawn_rate: i32,
 
+    /** Where trees and bushes are placed when the world is made. */
+    #[serde(default)]
+    pub forest: ForestConfig,
+
+    /** How bushes are eaten, grow back and spread. */
+    #[serde(default)]
+    pub bushes: BushConfig,
+
+    /** Kinds of plant caterpillars can eat. */
+    #[serde(default = "default_foods")]
+    pub foods: Vec<FoodTypeConfig>,
+
+    /** Climbing trees to eat from their canopies. */
+    #[serde(default)]
+    pub trees: TreeConfig,
+
+    /** How fussy caterpillars are about what they eat. */
+    #[serde(default)]
+    pub diet: DietConfig,
+
     pub tree_height: f32,
 
     /** Minimum length of a caterpillar. */
@@ -35,11 +58,928 @@ pub struct DynamicConfig {
 
     pub enable_shadows: bool,
 
+    /** How foliage and caterpillars are drawn. */
+    #[serde(default)]
+    pub rendering: RenderingConfig,
+
+    /** Whether the world is a passive sandbox or a game with a player caterpillar. */
+    #[serde(default)]
+    pub game_mode: GameMode,
+
+    /** Rules for game mode. Ignored in the sandbox. */
+    #[serde(default)]
+    pub game: GameConfig,
+
+    /** How the camera follows the selected caterpillar. */
+    #[serde(default)]
+    pub camera: CameraConfig,
+
+    /** Where screenshots and timelapse frames are saved. */
+    #[serde(default)]
+    pub capture: CaptureConfig,
+
+    /** Recording of runs for later playback. */
+    #[serde(default)]
+    pub replay: ReplayConfig,
+
+    /** Lines drawn behind each caterpillar showing where it has been. */
+    #[serde(default)]
+    pub trails: TrailConfig,
+
+    /** Overlay showing where caterpillars have been and eaten over the run. */
+    #[serde(default)]
+    pub heatmap: HeatmapConfig,
+
+    /** Sun and sky that follow the time of day, with caterpillars sleeping through the night. */
+    #[serde(default)]
+    pub day_night: DayNightConfig,
+
+    /** Rain, wind and seasons. */
+    #[serde(default)]
+    pub weather: WeatherConfig,
+
+    /** Birds that circle over the trees and swoop down on caterpillars. */
+    #[serde(default)]
+    pub predators: PredatorConfig,
+
+    /** How caterpillars behave around each other. */
+    #[serde(default)]
+    pub social: SocialConfig,
+
+    /** What caterpillars think about in each situation they find themselves in. */
+    #[serde(default)]
+    pub thinking: ThinkingConfig,
+
+    /** Thought bubbles that pop up above caterpillars. */
+    #[serde(default)]
+    pub speech_bubbles: SpeechBubbleConfig,
+
+    /** Names floating under each caterpillar. */
+    #[serde(default)]
+    pub name_labels: NameLabelConfig,
+
+    /** Colours and markings caterpillars are painted with. */
+    #[serde(default)]
+    pub appearance: AppearanceConfig,
+
     pub names: Vec<String>,
     pub thoughts: Vec<String>,
     pub child_thoughts: Vec<String>,
 }
 
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum GameMode {
+    #[default]
+    Sandbox,
+    Game,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct GameConfig {
+    /** Length of a game in seconds. */
+    pub duration: f32,
+
+    /** Leaves the player must eat to win. */
+    pub target_score: i32,
+
+    /** How far away AI caterpillars can spot a bush. */
+    pub ai_sight_radius: f32,
+
+    /** Turning speed of the player caterpillar, in radians per second. */
+    pub player_turn_speed: f32,
+
+    /** Speed of the player caterpillar. */
+    pub player_speed: f32,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        GameConfig {
+            duration: 180.0,
+            target_score: 50,
+            ai_sight_radius: 150.0,
+            player_turn_speed: 2.5,
+            player_speed: 30.0,
+        }
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CameraConfig {
+    /** How quickly the camera catches up with the followed caterpillar. Zero snaps to it. */
+    pub follow_damping: f32,
+
+    /** How far ahead of the followed caterpillar to look, in seconds of travel. */
+    pub look_ahead: f32,
+
+    /** Whether the camera swings round to stay behind the followed caterpillar. */
+    pub auto_orbit: bool,
+
+    /** How quickly the camera swings round when auto orbiting. */
+    pub auto_orbit_speed: f32,
+
+    /** Seconds taken to move the camera to a newly selected caterpillar. */
+    pub retarget_seconds: f32,
+}
+
+impl Default for CameraConfig {
+    fn default() -> Self {
+        CameraConfig {
+            follow_damping: 5.0,
+            look_ahead: 0.5,
+            auto_orbit: false,
+            auto_orbit_speed: 1.5,
+            retarget_seconds: 0.75,
+        }
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CaptureConfig {
+    /** Directory screenshots and timelapses are saved in. Not used on the web, where they are downloaded. */
+    pub output_directory: String,
+
+    /** Simulated seconds between timelapse frames. */
+    pub timelapse_interval: f32,
+}
+
+impl Default for CaptureConfig {
+    fn default() -> Self {
+        CaptureConfig {
+            output_directory: "captures".to_string(),
+            timelapse_interval: 5.0,
+        }
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ReplayConfig {
+    /** Whether runs are recorded so they can be watched again from the menu. */
+    pub record: bool,
+
+    /** Directory recorded runs are saved in. Not used on the web, where only the last run is kept. */
+    pub directory: String,
+
+    /** Simulated seconds between recorded frames. Playback is smoothed between them. */
+    pub sample_interval: f32,
+}
+
+impl Default for ReplayConfig {
+    fn default() -> Self {
+        ReplayConfig {
+            record: false,
+            directory: "replays".to_string(),
+            sample_interval: 0.1,
+        }
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TrailConfig {
+    /** Whether trails are shown when the world starts. T toggles them either way. */
+    pub enabled: bool,
+
+    /** Number of points in a trail. Older points fade out and are dropped. */
+    pub length: usize,
+
+    /** Distance a caterpillar moves before another point is added to its trail. */
+    pub point_spacing: f32,
+}
+
+impl Default for TrailConfig {
+    fn default() -> Self {
+        TrailConfig {
+            enabled: false,
+            length: 200,
+            point_spacing: 3.0,
+        }
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum RenderTechnique {
+    /** Everything drawn in full detail. */
+    Full,
+    /** Everything drawn with simpler meshes. */
+    LowPoly,
+    /** Simpler meshes further from the camera, and flat billboards for distant foliage. */
+    Lod,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RenderingConfig {
+    pub technique: RenderTechnique,
+
+    /** Distance from the camera beyond which simpler meshes are used, with the `lod` technique. */
+    pub low_detail_distance: f32,
+
+    /** Distance beyond which foliage is drawn as billboards, with the `lod` technique. */
+    pub billboard_distance: f32,
+
+    /** Distance beyond which nothing is drawn, with the `lod` technique. 0 draws everything. */
+    pub cull_distance: f32,
+}
+
+impl Default for RenderingConfig {
+    fn default() -> Self {
+        RenderingConfig {
+            technique: RenderTechnique::Lod,
+            low_detail_distance: 150.0,
+            billboard_distance: 500.0,
+            cull_distance: 2000.0,
+        }
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ForestConfig {
+    /** Closest two trees can be to each other. 0 places them anywhere. */
+    pub tree_spacing: f32,
+
+    /** Fraction of the starting bushes that grow in patches around trees. */
+    pub bush_cluster_fraction: f32,
+
+    /** How far from its tree a patch of bushes spreads. */
+    pub bush_cluster_radius: f32,
+
+    /** Number of open clearings with nothing growing in them. */
+    pub clearings: usize,
+
+    /** Radius of each clearing. */
+    pub clearing_radius: f32,
+
+    /** Open ground kept around where caterpillars start, beyond `startingCaterpillarRadius`. */
+    pub spawn_clearance: f32,
+}
+
+impl Default for ForestConfig {
+    fn default() -> Self {
+        ForestConfig {
+            tree_spacing: 40.0,
+            bush_cluster_fraction: 0.6,
+            bush_cluster_radius: 40.0,
+            clearings: 3,
+            clearing_radius: 60.0,
+            spawn_clearance: 10.0,
+        }
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BushConfig {
+    /** Food a caterpillar takes with each bite. */
+    pub bite_size: f32,
+
+    /** Simulated seconds between a caterpillar's bites. */
+    pub bite_interval: f32,
+
+    /** Chance each second that a fully grown bush drops a seed. */
+    pub seed_chance: f32,
+
+    /** Furthest a seed lands from its bush. */
+    pub seed_radius: f32,
+
+    /** Fraction of a full bush that one grown from a seed starts with. */
+    pub seedling_amount: f32,
+
+    /** Most bushes the world can hold. */
+    pub carrying_capacity: usize,
+}
+
+impl Default for BushConfig {
+    fn default() -> Self {
+        BushConfig {
+            bite_size: 1.0,
+            bite_interval: 1.0,
+            seed_chance: 0.01,
+            seed_radius: 30.0,
+            seedling_amount: 0.2,
+            carrying_capacity: 300,
+        }
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum FoodMesh {
+    Sphere,
+    Cube,
+    Capsule,
+    Torus,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FoodTypeConfig {
+    pub name: String,
+
+    /** Shape of the plant. */
+    pub mesh: FoodMesh,
+
+    /** Radius of a fully grown plant. */
+    pub size: f32,
+
+    /** Colour as red, green and blue between 0 and 1. */
+    pub colour: [f32; 3],
+
+    /** How much a caterpillar gains from each unit of food it eats. */
+    pub nutrition: f32,
+
+    /** Food in a fully grown plant. */
+    pub food_amount: f32,
+
+    /** Food a plant grows back each simulated second. */
+    pub regrowth_rate: f32,
+
+    /** How often this plant is picked when a new one spawns, relative to the others. */
+    pub spawn_weight: f32,
+}
+
+fn default_foods() -> Vec<FoodTypeConfig> {
+    vec![
+        FoodTypeConfig {
+            name: "Leaves".to_string(),
+            mesh: FoodMesh::Sphere,
+            size: 4.0,
+            colour: [0.0, 0.25, 0.0],
+            nutrition: 1.0,
+            food_amount: 3.0,
+            regrowth_rate: 0.05,
+            spawn_weight: 6.0,
+        },
+        FoodTypeConfig {
+            name: "Flowers".to_string(),
+            mesh: FoodMesh::Torus,
+            size: 3.0,
+            colour: [0.9, 0.4, 0.7],
+            nutrition: 2.0,
+            food_amount: 1.0,
+            regrowth_rate: 0.03,
+            spawn_weight: 3.0,
+        },
+        FoodTypeConfig {
+            name: "Fruit".to_string(),
+            mesh: FoodMesh::Sphere,
+            size: 2.0,
+            colour: [0.9, 0.45, 0.1],
+            nutrition: 4.0,
+            food_amount: 2.0,
+            regrowth_rate: 0.01,
+            spawn_weight: 1.0,
+        },
+    ]
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TreeConfig {
+    /** Whether caterpillars climb trees to eat from the canopy. */
+    pub edible: bool,
+
+    /** Food in the smallest canopy sphere. Bigger spheres hold more. */
+    pub canopy_food: f32,
+
+    /** How much a caterpillar gains from each unit of canopy it eats. */
+    pub nutrition: f32,
+
+    /** Food a canopy sphere grows back each simulated second. */
+    pub regrowth_rate: f32,
+
+    /** Fraction of its usual speed a caterpillar climbs at. */
+    pub climb_speed: f32,
+
+    /** Bites a caterpillar takes before climbing back down. */
+    pub bites_per_climb: u32,
+
+    /** Simulated seconds after climbing down before a caterpillar will climb again. */
+    pub climb_cooldown: f32,
+}
+
+impl Default for TreeConfig {
+    fn default() -> Self {
+        TreeConfig {
+            edible: true,
+            canopy_food: 4.0,
+            nutrition: 3.0,
+            regrowth_rate: 0.02,
+            climb_speed: 0.3,
+            bites_per_climb: 5,
+            climb_cooldown: 30.0,
+        }
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DietConfig {
+    /** How far a caterpillar's liking for each food can stray from 1, either way. */
+    pub variation: f32,
+
+    /** Caterpillars won't eat food they like less than this. */
+    pub refuse_below: f32,
+}
+
+impl Default for DietConfig {
+    fn default() -> Self {
+        DietConfig {
+            variation: 0.75,
+            refuse_below: 0.5,
+        }
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct HeatmapConfig {
+    /** Whether the heatmap is shown when the world starts. H toggles it either way. */
+    pub enabled: bool,
+
+    /** Number of cells along each side of the heatmap. */
+    pub resolution: usize,
+}
+
+impl Default for HeatmapConfig {
+    fn default() -> Self {
+        HeatmapConfig {
+            enabled: false,
+            resolution: 128,
+        }
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DayNightConfig {
+    /** Whether time passes. When off it is always midday. */
+    pub enabled: bool,
+
+    /** Simulated seconds in a full day and night. */
+    pub day_length: f32,
+
+    /** Time of day the world starts at, as a fraction of the day. 0 is midnight and 0.5 is noon. */
+    pub start_time: f32,
+}
+
+impl Default for DayNightConfig {
+    fn default() -> Self {
+        DayNightConfig {
+            enabled: false,
+            day_length: 120.0,
+            start_time: 0.3,
+        }
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct WeatherConfig {
+    /** Whether the weather and seasons change. */
+    pub enabled: bool,
+
+    /** Simulated seconds between chances for the weather to change. */
+    pub change_interval: f32,
+
+    /** Chance of rain each time the weather changes, between 0 and 1. */
+    pub rain_chance: f32,
+
+    /** Number of raindrops falling around the camera. */
+    pub rain_drops: usize,
+
+    /** Caterpillar speed is multiplied by this while it rains. */
+    pub rain_speed_multiplier: f32,
+
+    /** Bush spawn rate is multiplied by this while it rains. */
+    pub rain_growth_multiplier: f32,
+
+    /** How far tree canopies sway in the wind. */
+    pub wind_strength: f32,
+
+    /** How quickly tree canopies sway back and forth. */
+    pub wind_speed: f32,
+
+    /** Compass direction the wind blows towards, in degrees. */
+    pub wind_direction: f32,
+
+    /** Simulated seconds in each season. 0 stays in the first season. */
+    pub season_length: f32,
+
+    /** Seasons in the order they come round. */
+    pub seasons: Vec<SeasonConfig>,
+}
+
+impl Default for WeatherConfig {
+    fn default() -> Self {
+        WeatherConfig {
+            enabled: false,
+            change_interval: 30.0,
+            rain_chance: 0.3,
+            rain_drops: 500,
+            rain_speed_multiplier: 0.5,
+            rain_growth_multiplier: 2.0,
+            wind_strength: 1.5,
+            wind_speed: 1.2,
+            wind_direction: 0.0,
+            season_length: 120.0,
+            seasons: vec![
+                SeasonConfig {
+                    name: "Spring".to_string(),
+                    ground_colour: [0.33, 0.49, 0.27],
+                    tree_colour: [0.28, 0.37, 0.04],
+                    bush_tint: [0.0, 0.25, 0.0],
+                    bush_tint_strength: 0.0,
+                    bush_spawn_rate: 2,
+                },
+                SeasonConfig {
+                    name: "Summer".to_string(),
+                    ground_colour: [0.45, 0.52, 0.25],
+                    tree_colour: [0.2, 0.35, 0.05],
+                    bush_tint: [0.05, 0.3, 0.0],
+                    bush_tint_strength: 0.2,
+                    bush_spawn_rate: 1,
+                },
+                SeasonConfig {
+                    name: "Autumn".to_string(),
+                    ground_colour: [0.45, 0.38, 0.2],
+                    tree_colour: [0.7, 0.35, 0.05],
+                    bush_tint: [0.6, 0.35, 0.05],
+                    bush_tint_strength: 0.5,
+                    bush_spawn_rate: 1,
+                },
+                SeasonConfig {
+                    name: "Winter".to_string(),
+                    ground_colour: [0.85, 0.88, 0.9],
+                    tree_colour: [0.3, 0.3, 0.25],
+                    bush_tint: [0.6, 0.6, 0.55],
+                    bush_tint_strength: 0.4,
+                    bush_spawn_rate: 0,
+                },
+            ],
+        }
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SeasonConfig {
+    pub name: String,
+
+    /** Colour of the ground as red, green and blue between 0 and 1. */
+    pub ground_colour: [f32; 3],
+
+    /** Colour of tree canopies. */
+    pub tree_colour: [f32; 3],
+
+    /** Colour every kind of food is tinted towards. */
+    pub bush_tint: [f32; 3],
+
+    /** How far food is tinted, from 0 for its own colour to 1 for `bushTint`. */
+    #[serde(default)]
+    pub bush_tint_strength: f32,
+
+    /** Bushes spawned each second, in place of `bushSpawnRate`. */
+    pub bush_spawn_rate: i32,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PredatorConfig {
+    /** Number of birds. 0 leaves caterpillars in peace. */
+    pub count: usize,
+
+    /** Speed of a bird circling or climbing back up. */
+    pub speed: f32,
+
+    /** Speed of a bird swooping down on a caterpillar. */
+    pub swoop_speed: f32,
+
+    /** Simulated seconds a bird stays full after a meal before it hunts again. */
+    pub hunger: f32,
+
+    /** Height above the treetops birds circle at. */
+    pub altitude: f32,
+
+    /** Radius of the circles birds fly around a tree. */
+    pub circle_radius: f32,
+
+    /** How far across the ground a hungry bird can spot a caterpillar. */
+    pub sight_radius: f32,
+
+    /** Chance a bird only gets away with the tail segment rather than the whole caterpillar. */
+    pub tail_only_chance: f32,
+
+    /** How close a hunting bird has to be for caterpillars to run from it. */
+    pub flee_radius: f32,
+
+    /** Caterpillar speed is multiplied by this while fleeing. */
+    pub flee_speed_multiplier: f32,
+}
+
+impl Default for PredatorConfig {
+    fn default() -> Self {
+        PredatorConfig {
+            count: 0,
+            speed: 40.0,
+            swoop_speed: 90.0,
+            hunger: 60.0,
+            altitude: 40.0,
+            circle_radius: 50.0,
+            sight_radius: 150.0,
+            tail_only_chance: 0.5,
+            flee_radius: 80.0,
+            flee_speed_multiplier: 1.5,
+        }
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SocialConfig {
+    /** Whether caterpillars line up head to tail and follow each other in processions. */
+    pub procession: bool,
+
+    /** How close a caterpillar has to come to another's tail to start following it. */
+    pub join_radius: f32,
+
+    /** Gap a following caterpillar keeps behind the tail in front. */
+    pub follow_distance: f32,
+
+    /** Chance each second that a caterpillar wanders off from its procession. */
+    pub leave_chance: f32,
+
+    /** Whether caterpillars move away from crowds. */
+    pub avoid_crowding: bool,
+
+    /** How close other caterpillars have to be to count towards a crowd. */
+    pub crowd_radius: f32,
+
+    /** Number of caterpillars close by that makes a crowd. */
+    pub crowd_size: usize,
+
+    /** Whether caterpillars that reach the same bush fight over it. */
+    pub competition: bool,
+
+    /** Simulated seconds before the loser of a fight can eat, and before either fights again. */
+    pub contest_cooldown: f32,
+}
+
+impl Default for SocialConfig {
+    fn default() -> Self {
+        SocialConfig {
+            procession: false,
+            join_radius: 30.0,
+            follow_distance: 6.0,
+            leave_chance: 0.02,
+            avoid_crowding: true,
+            crowd_radius: 25.0,
+            crowd_size: 3,
+            competition: true,
+            contest_cooldown: 10.0,
+        }
+    }
+}
+
+fn strings(lines: &[&str]) -> Vec<String> {
+    lines.iter().map(|line| line.to_string()).collect()
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ThinkingConfig {
+    /** Simulated seconds between a caterpillar's thoughts while its situation stays the same. */
+    pub interval: f32,
+
+    /** Simulated seconds without food before a caterpillar gets hungry. */
+    pub hungry_after: f32,
+
+    /** Simulated seconds a caterpillar keeps thinking about its last meal. */
+    pub just_ate_for: f32,
+
+    /** How close another caterpillar has to be to be noticed. */
+    pub near_radius: f32,
+
+    /** Thoughts for each situation, finishing the sentence "is thinking about". `{name}` is
+    replaced with the caterpillar's name, `{food}` with the food it just ate or likes best, and
+    `{other}` with the caterpillar it is thinking about. An empty list falls back to `thoughts`. */
+    pub hungry: Vec<String>,
+    pub just_ate: Vec<String>,
+    pub near_caterpillar: Vec<String>,
+    pub night: Vec<String>,
+    pub fleeing: Vec<String>,
+    pub following: Vec<String>,
+    pub won_fight: Vec<String>,
+    pub lost_fight: Vec<String>,
+}
+
+impl Default for ThinkingConfig {
+    fn default() -> Self {
+        ThinkingConfig {
+            interval: 15.0,
+            hungry_after: 60.0,
+            just_ate_for: 10.0,
+            near_radius: 20.0,
+            hungry: strings(&["lunch.", "a nice bit of {food}.", "how hungry {name} is."]),
+            just_ate: strings(&[
+                "that delicious {food}.",
+                "having a lie down after all that {food}.",
+                "seconds.",
+            ]),
+            near_caterpillar: strings(&[
+                "saying hello to {other}.",
+                "whether {other} is after their {food}.",
+                "how close {other} is.",
+            ]),
+            night: strings(&["Zzz...", "dreams of {food}.", "dreams of wings."]),
+            fleeing: strings(&[
+                "that bird!",
+                "not being eaten.",
+                "how {name} isn't very tasty, honest.",
+            ]),
+            following: strings(&[
+                "following {other}.",
+                "where {other} is going.",
+                "how long {other} is.",
+            ]),
+            won_fight: strings(&[
+                "how they saw {other} off their {food}.",
+                "keeping all the {food} to themselves.",
+            ]),
+            lost_fight: strings(&["how rude {other} was.", "getting {other} back for that."]),
+        }
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SpeechBubbleConfig {
+    /** Whether a bubble pops up above a caterpillar each time its thought changes. */
+    pub enabled: bool,
+
+    /** Seconds a bubble stays up, including fading out. */
+    pub duration: f32,
+
+    /** Seconds a bubble takes to fade out at the end. */
+    pub fade_seconds: f32,
+
+    /** Bubbles aren't shown for caterpillars further than this from the camera. */
+    pub max_distance: f32,
+
+    pub font_size: f32,
+}
+
+impl Default for SpeechBubbleConfig {
+    fn default() -> Self {
+        SpeechBubbleConfig {
+            enabled: false,
+            duration: 6.0,
+            fade_seconds: 1.5,
+            max_distance: 300.0,
+            font_size: 16.0,
+        }
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct NameLabelConfig {
+    /** Whether names are shown from the start. L shows and hides them either way. */
+    pub enabled: bool,
+
+    pub font_size: f32,
+
+    /** Names of caterpillars closer than this to the camera are shown at full size. */
+    pub full_size_distance: f32,
+
+    /** Names shrink no smaller than this fraction of their full size. */
+    pub min_scale: f32,
+
+    /** Names aren't shown for caterpillars further than this from the camera. */
+    pub max_distance: f32,
+}
+
+impl Default for NameLabelConfig {
+    fn default() -> Self {
+        NameLabelConfig {
+            enabled: false,
+            font_size: 18.0,
+            full_size_distance: 100.0,
+            min_scale: 0.5,
+            max_distance: 500.0,
+        }
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AppearanceConfig {
+    /** Colour schemes a caterpillar is painted in, picked at random unless `byName` says otherwise. */
+    pub palettes: Vec<PaletteConfig>,
+
+    /** Palette to use for caterpillars with a given name, by the palette's name. */
+    pub by_name: HashMap<String, String>,
+
+    /** Segments in each stripe of a striped palette. 0 paints the body plain. */
+    pub stripe_width: i32,
+
+    /** Whether caterpillars have spots, in palettes that give a spot colour. */
+    pub spots: bool,
+
+    /** Whether caterpillars have tufts of hair, in palettes that give a hair colour. */
+    pub hair: bool,
+}
+
+impl Default for AppearanceConfig {
+    fn default() -> Self {
+        AppearanceConfig {
+            palettes: vec![
+                PaletteConfig::default(),
+                PaletteConfig {
+                    name: "Monarch".to_string(),
+                    head: [0.1, 0.1, 0.1],
+                    nose: [1.0, 0.85, 0.1],
+                    body: [0.95, 0.95, 0.9],
+                    stripe: Some([0.1, 0.1, 0.1]),
+                    feet: [0.1, 0.1, 0.1],
+                    spots: Some([1.0, 0.85, 0.1]),
+                    hair: None,
+                },
+                PaletteConfig {
+                    name: "Tiger".to_string(),
+                    head: [0.2, 0.1, 0.05],
+                    nose: [0.9, 0.45, 0.1],
+                    body: [0.9, 0.45, 0.1],
+                    stripe: Some([0.2, 0.1, 0.05]),
+                    feet: [0.2, 0.1, 0.05],
+                    spots: None,
+                    hair: Some([0.35, 0.2, 0.1]),
+                },
+                PaletteConfig {
+                    name: "Emerald".to_string(),
+                    head: [0.2, 0.5, 0.1],
+                    nose: [0.9, 0.9, 0.3],
+                    body: [0.3, 0.7, 0.2],
+                    stripe: Some([0.45, 0.85, 0.3]),
+                    feet: [0.1, 0.3, 0.05],
+                    spots: Some([0.95, 0.95, 0.9]),
+                    hair: None,
+                },
+            ],
+            by_name: HashMap::new(),
+            stripe_width: 1,
+            spots: true,
+            hair: true,
+        }
+    }
+}
+
+/// A caterpillar's colours, each as red, green and blue between 0 and 1.
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PaletteConfig {
+    pub name: String,
+
+    pub head: [f32; 3],
+
+    pub nose: [f32; 3],
+
+    pub body: [f32; 3],
+
+    /** Colour of every other stripe along the body. Left out, the body is one colour. */
+    pub stripe: Option<[f32; 3]>,
+
+    pub feet: [f32; 3],
+
+    /** Colour of spots on the body. Left out, the caterpillar has none. */
+    pub spots: Option<[f32; 3]>,
+
+    /** Colour of tufts of hair on the body. Left out, the caterpillar has none. */
+    pub hair: Option<[f32; 3]>,
+}
+
+impl Default for PaletteConfig {
+    fn default() -> Self {
+        PaletteConfig {
+            name: "Classic".to_string(),
+            head: [1.0, 0.0, 0.0],
+            nose: [1.0, 0.0, 1.0],
+            body: [0.8, 0.7, 0.6],
+            stripe: None,
+            feet: [0.0, 0.0, 0.0],
+            spots: None,
+            hair: None,
+        }
+    }
+}
+
 #[derive(Resource)]
 pub struct DynamicConfigHandleHolder(Handle<DynamicConfig>);
 
@@ -48,18 +988,42 @@ pub fn create_dynamic_config(
     asset_server: Res<AssetServer>,
     mut app_state: ResMut<State<AppState>>,
 ) {
-    let mut data_file: String = "data.json".to_string();
-    let args: Vec<String> = env::args().collect();
-    for arg in args {
-        if arg.ends_with(".json") {
-            data_file = arg.clone();
+    // a replay named on the command line is played straight away.
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(path) = env::args().find_map(|arg| arg.strip_prefix("--replay=").map(String::from))
+    {
+        match replay::load_replay_file(&path) {
+            Ok(loaded) => {
+                commands.insert_resource(replay::LastReplay(loaded));
+                app_state.set(AppState::ReplayLoad).unwrap();
+                return;
+            }
+            Err(e) => error!("Could not load replay {}: {}", path, e),
         }
     }
+
+    // a config named on the command line skips the menu.
+    let data_file = env::args()
+        .filter(|arg| arg.ends_with(".json") && !arg.starts_with("--"))
+        .last();
+    if let Some(data_file) = data_file {
+        request_dynamic_config(&mut commands, &asset_server, &data_file);
+        app_state.set(AppState::ConfigLoad).unwrap();
+    } else {
+        app_state.set(AppState::Menu).unwrap();
+    }
+}
+
+/// Starts loading the given config file. Move to `AppState::ConfigLoad` to wait for it.
+pub fn request_dynamic_config(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    data_file: &str,
+) {
     info!("Using data from {}.", data_file);
 
-    let data_handle: Handle<DynamicConfig> = asset_server.load(data_file.as_str());
+    let data_handle: Handle<DynamicConfig> = asset_server.load(data_file);
     commands.insert_resource(DynamicConfigHandleHolder(data_handle));
-    app_state.set(AppState::ConfigLoad).unwrap();
     info!("Config created.");
 }
 
@@ -67,10 +1031,11 @@ pub fn load_dynamic_config(
     mut commands: Commands,
     mut app_state: ResMut<State<AppState>>,
     handle: Res<DynamicConfigHandleHolder>,
-    mut dynamic_config_assets: ResMut<Assets<DynamicConfig>>,
+    dynamic_config_assets: Res<Assets<DynamicConfig>>,
 ) {
-    if let Some(dynamic_config) = dynamic_config_assets.remove(handle.0.id()) {
-        commands.insert_resource(dynamic_config);
+    // the asset is cloned rather than removed so the same file can be loaded again from the menu.
+    if let Some(dynamic_config) = dynamic_config_assets.get(&handle.0) {
+        commands.insert_resource(dynamic_config.clone());
         app_state.set(AppState::PreLoad).unwrap();
         info!("Config loaded.");
     } else {
@@ -78,3 +1043,27 @@ pub fn load_dynamic_config(
         error!("Dynamic config could not be loaded.");
     }
 }
+
+/// A config with only the fields that have no default set, so tests don't depend on the configs
+/// in the assets folder.
+#[cfg(test)]
+pub fn test_config() -> DynamicConfig {
+    serde_json::from_value(serde_json::json!({
+        "planeSize": 500.0,
+        "startingCaterpillars": 1,
+        "startingCaterpillarRadius": 0.0,
+        "startingBushes": 0,
+        "startingTrees": 0,
+        "bushSpawnRate": 0,
+        "treeHeight": 100.0,
+        "caterpillarMinLength": 10,
+        "caterpillarMaxLength": 20,
+        "caterpillarMinSpeed": 5.0,
+        "caterpillarMaxSpeed": 10.0,
+        "enableShadows": false,
+        "names": ["Test"],
+        "thoughts": ["testing."],
+        "childThoughts": ["small testing."]
+    }))
+    .unwrap()
+}
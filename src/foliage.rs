@@ -1,19 +1,139 @@
 use bevy::prelude::*;
 
-use crate::{dynamic_config::DynamicConfig, random};
+use crate::{
+    day_night::DayNight,
+    dynamic_config::{DynamicConfig, FoodMesh, FoodTypeConfig},
+    forest::Forest,
+    lod::LodMeshes,
+    random,
+    simulation::SimulationTime,
+    weather::Weather,
+};
 
+/// A half eaten plant is still drawn at least this big, so it doesn't vanish before it's gone.
+const MIN_BUSH_SCALE: f32 = 0.3;
+
+/// A plant that can be eaten. It shrinks as it is eaten and grows back over time.
+#[derive(Component)]
+pub struct Food {
+    /// Index into the configured food types.
+    pub kind: usize,
+    pub amount: f32,
+    pub max_amount: f32,
+}
+
+impl Food {
+    pub fn is_full(&self) -> bool {
+        self.amount >= self.max_amount
+    }
+
+    /// Scales and sinks a plant's transform to match how much of it is left.
+    pub fn size_transform(&self, transform: &mut Transform, food_type: &FoodTypeConfig) {
+        let fraction = if self.max_amount > 0.0 {
+            (self.amount / self.max_amount).clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+        let scale = MIN_BUSH_SCALE + (1.0 - MIN_BUSH_SCALE) * fraction;
+        scale_transform(transform, food_type, scale);
+    }
+}
+
+/// Scales a plant's transform, sinking it so it still sits on the ground.
+pub fn scale_transform(transform: &mut Transform, food_type: &FoodTypeConfig, scale: f32) {
+    transform.scale = Vec3::splat(scale);
+    transform.translation.y = ground_offset(food_type) * scale;
+}
+
+/// Builds the mesh for a food type, with fewer faces when `detailed` is false.
+fn food_mesh(food_type: &FoodTypeConfig, detailed: bool) -> Mesh {
+    let size = food_type.size;
+    let (sectors, stacks) = if detailed { (16, 16) } else { (6, 4) };
+    match food_type.mesh {
+        FoodMesh::Sphere => Mesh::from(shape::UVSphere {
+            radius: size,
+            stacks,
+            sectors,
+        }),
+        FoodMesh::Cube => Mesh::from(shape::Cube { size: size * 2.0 }),
+        FoodMesh::Capsule => Mesh::from(shape::Capsule {
+            radius: size / 2.0,
+            depth: size,
+            latitudes: stacks,
+            longitudes: sectors * 2,
+            ..default()
+        }),
+        FoodMesh::Torus => Mesh::from(shape::Torus {
+            radius: size * 0.75,
+            ring_radius: size * 0.25,
+            subdivisions_segments: sectors * 2,
+            subdivisions_sides: stacks + 2,
+        }),
+    }
+}
+
+/// How high a fully grown plant's centre sits. Spheres and cubes are half buried, as bushes
+/// always have been.
+fn ground_offset(food_type: &FoodTypeConfig) -> f32 {
+    match food_type.mesh {
+        FoodMesh::Sphere | FoodMesh::Cube => food_type.size / 2.0,
+        FoodMesh::Capsule => food_type.size,
+        FoodMesh::Torus => food_type.size * 0.25,
+    }
+}
+
+/// Picks a food type at random, weighted by how often each spawns.
+pub fn random_food_kind(config: &DynamicConfig) -> Option<usize> {
+    let total: f32 = config
+        .foods
+        .iter()
+        .map(|food_type| food_type.spawn_weight.max(0.0))
+        .sum();
+    if total <= 0.0 {
+        return None;
+    }
+    let mut pick = random::range_f32(0.0, total);
+    for (kind, food_type) in config.foods.iter().enumerate() {
+        pick -= food_type.spawn_weight.max(0.0);
+        if pick < 0.0 {
+            return Some(kind);
+        }
+    }
+    Some(config.foods.len() - 1)
+}
+
+/// Sent each time a caterpillar takes a bite out of a bush.
+pub struct FoodEatenEvent {
+    pub position: Vec3,
+    /// The bush bitten, or none for a tree's canopy.
+    pub food: Option<Entity>,
+}
+
+/// Tags the trunk of a tree, the canopy spheres are its children.
 #[derive(Component)]
-pub struct Food;
+pub struct Tree;
+
+/// One of the spheres making up a tree's canopy. Remembers where it rests so the wind can
+/// sway it about that point.
+#[derive(Component)]
+pub struct Canopy {
+    pub rest: Vec3,
+    pub phase: f32,
+}
+
+/// Paces `spawn_bushes`. Only ticked while the level is playing, so pausing stops spawning.
+#[derive(Resource)]
+pub struct BushSpawnTimer(pub Timer);
 
 #[derive(Resource)]
 pub struct FoliageAssets {
     pub tree_box_handle: Handle<Mesh>,
     pub tree_trunk_material_handle: Handle<StandardMaterial>,
-    pub tree_green_mesh_handle: Handle<Mesh>,
+    pub tree_green_meshes: LodMeshes,
     pub tree_green_material_handle: Handle<StandardMaterial>,
 
-    pub bush_handle: Handle<Mesh>,
-    pub bush_material_handle: Handle<StandardMaterial>,
+    /// Mesh and material for each food type, in the order they're configured.
+    pub food_handles: Vec<(LodMeshes, Handle<StandardMaterial>)>,
 }
 
 pub fn setup_foliage_assets(
@@ -28,35 +148,64 @@ pub fn setup_foliage_assets(
         perceptual_roughness: 1.0,
         ..default()
     });
-    let tree_green_mesh_handle = meshes.add(Mesh::from(shape::UVSphere {
-        radius: 8.0,
-        stacks: 16,
-        sectors: 16,
-    }));
+    let technique = config.rendering.technique;
+    let tree_green_meshes = LodMeshes::new(
+        &mut meshes,
+        technique,
+        Mesh::from(shape::UVSphere {
+            radius: 8.0,
+            stacks: 16,
+            sectors: 16,
+        }),
+        Mesh::from(shape::UVSphere {
+            radius: 8.0,
+            stacks: 4,
+            sectors: 6,
+        }),
+        Some(Mesh::from(shape::Circle {
+            radius: 8.0,
+            vertices: 12,
+        })),
+    );
     let tree_green_material_handle = materials.add(StandardMaterial {
         base_color: Color::rgb(0.28, 0.37, 0.04),
         perceptual_roughness: 1.0,
         ..default()
     });
 
-    let bush_handle = meshes.add(Mesh::from(shape::UVSphere {
-        radius: 4.0,
-        stacks: 16,
-        sectors: 16,
-    }));
-    let bush_material_handle = materials.add(StandardMaterial {
-        base_color: Color::rgb(0.0, 0.25, 0.0),
-        perceptual_roughness: 1.0,
-        ..default()
-    });
+    let food_handles = config
+        .foods
+        .iter()
+        .map(|food_type| {
+            let material = StandardMaterial {
+                base_color: Color::rgb(
+                    food_type.colour[0],
+                    food_type.colour[1],
+                    food_type.colour[2],
+                ),
+                perceptual_roughness: 1.0,
+                ..default()
+            };
+            let food_meshes = LodMeshes::new(
+                &mut meshes,
+                technique,
+                food_mesh(food_type, true),
+                food_mesh(food_type, false),
+                Some(Mesh::from(shape::Circle {
+                    radius: food_type.size,
+                    vertices: 8,
+                })),
+            );
+            (food_meshes, materials.add(material))
+        })
+        .collect();
 
     let foliage_assets = FoliageAssets {
         tree_box_handle,
         tree_trunk_material_handle,
-        tree_green_mesh_handle,
+        tree_green_meshes,
         tree_green_material_handle,
-        bush_handle,
-        bush_material_handle,
+        food_handles,
     };
     commands.insert_resource(foliage_assets);
 }
@@ -66,29 +215,53 @@ pub fn setup_foliage(
     foliage_assets: Res<FoliageAssets>,
     config: Res<DynamicConfig>,
 ) {
-    for _ in 0..config.starting_trees {
-        commands = create_tree_prv(
-            commands,
-            &foliage_assets,
-            config.plane_size,
-            config.tree_height,
-        );
+    commands.insert_resource(BushSpawnTimer(Timer::from_seconds(
+        1.0,
+        TimerMode::Repeating,
+    )));
+
+    let forest = Forest::new(&config);
+    let tree_positions = forest.tree_positions(
+        config.starting_trees.max(0) as usize,
+        config.forest.tree_spacing,
+    );
+    for position in tree_positions.iter() {
+        commands = create_tree_prv(commands, &foliage_assets, *position, config.tree_height);
     }
 
-    for _ in 0..config.starting_bushes {
-        commands = create_bush_prv(commands, &foliage_assets, config.plane_size);
+    let bush_positions = forest.bush_positions(
+        config.starting_bushes.max(0) as usize,
+        &tree_positions,
+        &config,
+    );
+    for position in bush_positions {
+        commands = create_bush_prv(commands, &foliage_assets, &config, position);
     }
+    commands.insert_resource(forest);
 }
 
 fn create_tree_prv<'w, 's>(
     mut commands: Commands<'w, 's>,
     foliage_assets: &Res<FoliageAssets>,
-    plane_size: f32,
+    position: Vec3,
     tree_height: f32,
 ) -> Commands<'w, 's> {
-    let mut starting_vec = random::vec3(plane_size / 2.0);
+    let mut starting_vec = position;
     starting_vec.y = tree_height / 2.0;
-    let starting_transform = Transform::default().with_translation(starting_vec);
+    spawn_tree(&mut commands, foliage_assets, starting_vec, tree_height);
+    commands
+}
+
+/// Spawns a tree with its trunk centred on the given translation.
+pub fn spawn_tree(
+    commands: &mut Commands,
+    foliage_assets: &FoliageAssets,
+    translation: Vec3,
+    tree_height: f32,
+) -> Entity {
+    let starting_transform = Transform::default().with_translation(translation);
+    // trees sway out of step with each other.
+    let phase = random::range_f32(0.0, std::f32::consts::TAU);
 
     let mut tree_sphere_tranform_1 = Transform::from_xyz(0.0, tree_height / 2.0, 0.0);
     tree_sphere_tranform_1.scale = Vec3::new(1.0, 1.0, 1.0);
@@ -106,69 +279,314 @@ fn create_tree_prv<'w, 's>(
             transform: starting_transform,
             ..default()
         })
+        .insert(Tree)
         .with_children(|parent| {
-            parent.spawn(PbrBundle {
-                mesh: foliage_assets.tree_green_mesh_handle.clone(),
+            let mut canopy = parent.spawn(PbrBundle {
+                mesh: foliage_assets.tree_green_meshes.mesh(),
                 material: foliage_assets.tree_green_material_handle.clone(),
                 transform: tree_sphere_tranform_1,
                 ..default()
             });
-            parent.spawn(PbrBundle {
-                mesh: foliage_assets.tree_green_mesh_handle.clone(),
+            canopy.insert(Canopy {
+                rest: tree_sphere_tranform_1.translation,
+                phase,
+            });
+            foliage_assets.tree_green_meshes.insert_lod(&mut canopy);
+            let mut canopy = parent.spawn(PbrBundle {
+                mesh: foliage_assets.tree_green_meshes.mesh(),
                 material: foliage_assets.tree_green_material_handle.clone(),
                 transform: tree_sphere_tranform_2,
                 ..default()
             });
-            parent.spawn(PbrBundle {
-                mesh: foliage_assets.tree_green_mesh_handle.clone(),
+            canopy.insert(Canopy {
+                rest: tree_sphere_tranform_2.translation,
+                phase,
+            });
+            foliage_assets.tree_green_meshes.insert_lod(&mut canopy);
+            let mut canopy = parent.spawn(PbrBundle {
+                mesh: foliage_assets.tree_green_meshes.mesh(),
                 material: foliage_assets.tree_green_material_handle.clone(),
                 transform: tree_sphere_tranform_3,
                 ..default()
             });
-        });
-    commands
+            canopy.insert(Canopy {
+                rest: tree_sphere_tranform_3.translation,
+                phase,
+            });
+            foliage_assets.tree_green_meshes.insert_lod(&mut canopy);
+        })
+        .id()
 }
 
 #[allow(dead_code)]
 pub fn create_tree(
     commands: Commands,
     foliage_assets: Res<FoliageAssets>,
+    forest: Res<Forest>,
     config: Res<DynamicConfig>,
 ) {
-    create_tree_prv(
-        commands,
-        &foliage_assets,
-        config.plane_size,
-        config.tree_height,
-    );
+    if let Some(position) = forest.random_position() {
+        create_tree_prv(commands, &foliage_assets, position, config.tree_height);
+    }
 }
 
 fn create_bush_prv<'w, 's>(
     mut commands: Commands<'w, 's>,
     foliage_assets: &Res<FoliageAssets>,
-    plane_size: f32,
+    config: &DynamicConfig,
+    position: Vec3,
 ) -> Commands<'w, 's> {
-    let mut starting_vec = random::vec3(plane_size / 2.0);
-    starting_vec.y = 4.0 / 2.0;
-    let starting_transform = Transform::default().with_translation(starting_vec);
-
-    commands
-        .spawn(PbrBundle {
-            mesh: foliage_assets.bush_handle.clone(),
-            material: foliage_assets.bush_material_handle.clone(),
-            transform: starting_transform,
-            ..default()
-        })
-        .insert(Food);
+    if let Some(kind) = random_food_kind(config) {
+        spawn_food_bush(&mut commands, foliage_assets, config, position, kind, 1.0);
+    }
     commands
 }
 
+/// Spawns the mesh of a plant of the given food type. It only becomes edible once `Food` is
+/// added.
+pub fn spawn_bush(
+    commands: &mut Commands,
+    foliage_assets: &FoliageAssets,
+    translation: Vec3,
+    kind: usize,
+) -> Entity {
+    let Some((food_meshes, material)) = foliage_assets
+        .food_handles
+        .get(kind)
+        .or_else(|| foliage_assets.food_handles.first())
+    else {
+        // no food types are configured, so there is nothing to draw.
+        return commands
+            .spawn(SpatialBundle::from_transform(Transform::from_translation(
+                translation,
+            )))
+            .id();
+    };
+    let mut bush = commands.spawn(PbrBundle {
+        mesh: food_meshes.mesh(),
+        material: material.clone(),
+        transform: Transform::default().with_translation(translation),
+        ..default()
+    });
+    food_meshes.insert_lod(&mut bush);
+    bush.id()
+}
+
+/// Spawns an edible plant on the ground at the given position, starting with a fraction of the
+/// food a fully grown one holds.
+fn spawn_food_bush(
+    commands: &mut Commands,
+    foliage_assets: &FoliageAssets,
+    config: &DynamicConfig,
+    position: Vec3,
+    kind: usize,
+    fraction: f32,
+) -> Entity {
+    let food_type = &config.foods[kind];
+    let food = Food {
+        kind,
+        amount: food_type.food_amount * fraction.clamp(0.0, 1.0),
+        max_amount: food_type.food_amount,
+    };
+    let mut transform = Transform::from_xyz(position.x, 0.0, position.z);
+    food.size_transform(&mut transform, food_type);
+
+    let bush_entity = spawn_bush(commands, foliage_assets, transform.translation, kind);
+    commands.entity(bush_entity).insert(transform).insert(food);
+    bush_entity
+}
+
+/// How many of `wanted` new plants fit in a world holding `count` of its `capacity`.
+fn random_spawns(wanted: i32, count: i32, capacity: i32) -> i32 {
+    wanted.min(capacity - count).max(0)
+}
+
+/// How much room a world holding `count` of its `capacity` plants has left for seeds to take,
+/// from 1 when it's empty down to 0 when it's full.
+fn seed_room(count: i32, capacity: i32) -> f32 {
+    (1.0 - count as f32 / capacity as f32).max(0.0)
+}
+
+/// Spawns new plants at random and lets fully grown plants drop seeds of their own kind nearby.
+/// Both slow down as the world fills up towards its carrying capacity.
+#[allow(clippy::too_many_arguments)]
 pub fn spawn_bushes(
     mut commands: Commands,
+    time: Res<SimulationTime>,
+    mut spawn_timer: ResMut<BushSpawnTimer>,
     foliage_assets: Res<FoliageAssets>,
     config: Res<DynamicConfig>,
+    day_night: Res<DayNight>,
+    weather: Res<Weather>,
+    forest: Res<Forest>,
+    bush_query: Query<(&Transform, &Food)>,
 ) {
-    for _ in 0..config.bush_spawn_rate {
-        commands = create_bush_prv(commands, &foliage_assets, config.plane_size);
+    // at high simulation speeds the timer can finish more than once in a frame.
+    let seconds = spawn_timer.0.tick(time.delta()).times_finished_this_tick() as i32;
+    // bushes only grow back while the sun is up.
+    if seconds == 0 || day_night.is_night() {
+        return;
     }
+
+    let bush_config = &config.bushes;
+    let capacity = bush_config.carrying_capacity.max(1) as i32;
+    let mut count = bush_query.iter().count() as i32;
+
+    let spawns = random_spawns(weather.bush_spawn_rate(&config) * seconds, count, capacity);
+    for _ in 0..spawns {
+        if let Some(position) = forest.random_position() {
+            commands = create_bush_prv(commands, &foliage_assets, &config, position);
+            count += 1;
+        }
+    }
+
+    let half_size = config.plane_size / 2.0;
+    for (transform, food) in bush_query.iter() {
+        if !food.is_full() {
+            continue;
+        }
+        for _ in 0..seconds {
+            if random::range_f32(0.0, 1.0) >= bush_config.seed_chance * seed_room(count, capacity) {
+                continue;
+            }
+            let offset = random::vec3(bush_config.seed_radius);
+            let position = (transform.translation + offset).clamp(
+                Vec3::new(-half_size, 0.0, -half_size),
+                Vec3::new(half_size, 0.0, half_size),
+            );
+            // seeds landing in a clearing don't take.
+            if forest.is_open(position) {
+                continue;
+            }
+            spawn_food_bush(
+                &mut commands,
+                &foliage_assets,
+                &config,
+                position,
+                food.kind,
+                bush_config.seedling_amount,
+            );
+            count += 1;
+        }
+    }
+}
+
+/// Grows plants back towards full size during the day, faster in the rain.
+pub fn bush_growth_system(
+    time: Res<SimulationTime>,
+    config: Res<DynamicConfig>,
+    day_night: Res<DayNight>,
+    weather: Res<Weather>,
+    mut bush_query: Query<(&mut Transform, &mut Food)>,
+) {
+    if day_night.is_night() || time.delta_seconds() == 0.0 {
+        return;
+    }
+
+    let mut growth = time.delta_seconds();
+    if weather.raining {
+        growth *= config.weather.rain_growth_multiplier;
+    }
+    for (mut transform, mut food) in bush_query.iter_mut() {
+        if food.is_full() {
+            continue;
+        }
+        let food_type = &config.foods[food.kind];
+        food.amount = (food.amount + food_type.regrowth_rate * growth).min(food.max_amount);
+        food.size_transform(&mut transform, food_type);
+    }
+}
+
+#[test]
+#[cfg(test)]
+fn size_transform_test_1() {
+    let config = crate::dynamic_config::test_config();
+    let food = Food {
+        kind: 0,
+        amount: -1.0,
+        max_amount: 10.0,
+    };
+    let mut transform = Transform::default();
+
+    food.size_transform(&mut transform, &config.foods[0]);
+
+    // Check resulting changes
+    assert_eq!(transform.scale, Vec3::splat(MIN_BUSH_SCALE));
+}
+
+#[test]
+#[cfg(test)]
+fn size_transform_test_2() {
+    let config = crate::dynamic_config::test_config();
+    let food = Food {
+        kind: 0,
+        amount: 20.0,
+        max_amount: 10.0,
+    };
+    let mut transform = Transform::default();
+
+    food.size_transform(&mut transform, &config.foods[0]);
+
+    // Check resulting changes
+    assert_eq!(transform.scale, Vec3::ONE);
+}
+
+#[test]
+#[cfg(test)]
+fn random_food_kind_test_1() {
+    let mut config = crate::dynamic_config::test_config();
+    for (kind, weight) in [0.0, -2.0, 0.0].into_iter().enumerate() {
+        config.foods[kind].spawn_weight = weight;
+    }
+
+    let kind = random_food_kind(&config);
+
+    // Check resulting changes
+    assert_eq!(kind, None);
+}
+
+#[test]
+#[cfg(test)]
+fn random_food_kind_test_2() {
+    let mut config = crate::dynamic_config::test_config();
+    config.foods.clear();
+
+    let kind = random_food_kind(&config);
+
+    // Check resulting changes
+    assert_eq!(kind, None);
+}
+
+#[test]
+#[cfg(test)]
+fn random_food_kind_test_3() {
+    let mut config = crate::dynamic_config::test_config();
+    for (kind, weight) in [0.0, -2.0, 1.0].into_iter().enumerate() {
+        config.foods[kind].spawn_weight = weight;
+    }
+
+    // Check resulting changes
+    for _ in 0..100 {
+        assert_eq!(random_food_kind(&config), Some(2));
+    }
+}
+
+#[test]
+#[cfg(test)]
+fn random_spawns_test_1() {
+    // Check resulting changes
+    assert_eq!(random_spawns(5, 0, 300), 5);
+    assert_eq!(random_spawns(5, 298, 300), 2);
+    assert_eq!(random_spawns(5, 300, 300), 0);
+    assert_eq!(random_spawns(5, 310, 300), 0);
+}
+
+#[test]
+#[cfg(test)]
+fn seed_room_test_1() {
+    // Check resulting changes
+    assert_eq!(seed_room(0, 300), 1.0);
+    assert_eq!(seed_room(150, 300), 0.5);
+    assert_eq!(seed_room(300, 300), 0.0);
+    assert_eq!(seed_room(310, 300), 0.0);
 }
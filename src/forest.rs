@@ -0,0 +1,209 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+
+use crate::{dynamic_config::DynamicConfig, random};
+
+/// Times a position is tried before giving up on it.
+const PLACEMENT_ATTEMPTS: usize = 30;
+
+/// Closest a bush in a patch grows to the middle of its tree, so it stays clear of the trunk.
+const TRUNK_CLEARANCE: f32 = 6.0;
+
+/// Open ground in the world, kept clear of trees and bushes.
+#[derive(Resource)]
+pub struct Forest {
+    half_size: f32,
+    /// Centre and radius of each clearing.
+    clearings: Vec<(Vec3, f32)>,
+    /// Distance from the middle of the world, where caterpillars start, kept clear.
+    spawn_clearance: f32,
+}
+
+impl Forest {
+    pub fn new(config: &DynamicConfig) -> Self {
+        let half_size = config.plane_size / 2.0;
+        let clearings = (0..config.forest.clearings)
+            .map(|_| {
+                let mut centre = random::vec3(half_size);
+                centre.y = 0.0;
+                (centre, config.forest.clearing_radius)
+            })
+            .collect();
+        Forest {
+            half_size,
+            clearings,
+            spawn_clearance: config.starting_caterpillar_radius + config.forest.spawn_clearance,
+        }
+    }
+
+    /// Whether a position is on open ground, where nothing should grow.
+    pub fn is_open(&self, position: Vec3) -> bool {
+        let flat = Vec3::new(position.x, 0.0, position.z);
+        flat.length() < self.spawn_clearance
+            || self
+                .clearings
+                .iter()
+                .any(|(centre, radius)| flat.distance(*centre) < *radius)
+    }
+
+    fn in_bounds(&self, position: Vec3) -> bool {
+        position.x.abs() <= self.half_size && position.z.abs() <= self.half_size
+    }
+
+    /// A random position on the ground that isn't open, if one can be found.
+    pub fn random_position(&self) -> Option<Vec3> {
+        (0..PLACEMENT_ATTEMPTS)
+            .map(|_| {
+                let mut position = random::vec3(self.half_size);
+                position.y = 0.0;
+                position
+            })
+            .find(|position| !self.is_open(*position))
+    }
+
+    /// Places up to `count` trees at random, no two closer than `spacing`. The world may fill up
+    /// before all of them fit.
+    pub fn tree_positions(&self, count: usize, spacing: f32) -> Vec<Vec3> {
+        if spacing <= 0.0 {
+            return (0..count).filter_map(|_| self.random_position()).collect();
+        }
+
+        // a grid of cells small enough to hold at most one tree each, so only the neighbouring
+        // cells need checking for trees that are too close.
+        let cell_size = spacing / std::f32::consts::SQRT_2;
+        let cells = ((self.half_size * 2.0 / cell_size).ceil() as usize).max(1);
+        let mut grid: Vec<Option<usize>> = vec![None; cells * cells];
+        let cell_of = |position: Vec3| {
+            let x = ((position.x + self.half_size) / cell_size) as usize;
+            let z = ((position.z + self.half_size) / cell_size) as usize;
+            (x.min(cells - 1), z.min(cells - 1))
+        };
+
+        let mut positions: Vec<Vec3> = Vec::with_capacity(count);
+        for _ in 0..(count * PLACEMENT_ATTEMPTS) {
+            if positions.len() >= count {
+                break;
+            }
+            let Some(candidate) = self.random_position() else {
+                continue;
+            };
+            let (x, z) = cell_of(candidate);
+            let too_close = (x.saturating_sub(2)..(x + 3).min(cells)).any(|nx| {
+                (z.saturating_sub(2)..(z + 3).min(cells)).any(|nz| {
+                    grid[nz * cells + nx].map_or(false, |index| {
+                        positions[index].distance(candidate) < spacing
+                    })
+                })
+            });
+            if too_close {
+                continue;
+            }
+            grid[z * cells + x] = Some(positions.len());
+            positions.push(candidate);
+        }
+
+        if positions.len() < count {
+            warn!(
+                "Only room for {} of {} trees with a spacing of {}.",
+                positions.len(),
+                count,
+                spacing
+            );
+        }
+        positions
+    }
+
+    /// Places bushes, some in patches around the trees and the rest scattered anywhere.
+    pub fn bush_positions(
+        &self,
+        count: usize,
+        trees: &[Vec3],
+        config: &DynamicConfig,
+    ) -> Vec<Vec3> {
+        let forest_config = &config.forest;
+        let clustered = if trees.is_empty() {
+            0
+        } else {
+            (count as f32 * forest_config.bush_cluster_fraction.clamp(0.0, 1.0)) as usize
+        };
+
+        let mut positions = Vec::with_capacity(count);
+        for _ in 0..clustered {
+            let tree = trees[random::range_i32(0, trees.len() as i32) as usize];
+            let position = (0..PLACEMENT_ATTEMPTS)
+                .map(|_| {
+                    // square root spreads bushes evenly over the patch rather than bunching
+                    // them in the middle.
+                    let distance = TRUNK_CLEARANCE
+                        + (forest_config.bush_cluster_radius - TRUNK_CLEARANCE).max(0.0)
+                            * random::range_f32(0.0, 1.0).sqrt();
+                    let angle = random::range_f32(0.0, TAU);
+                    Vec3::new(
+                        tree.x + angle.cos() * distance,
+                        0.0,
+                        tree.z + angle.sin() * distance,
+                    )
+                })
+                .find(|position| self.in_bounds(*position) && !self.is_open(*position));
+            if let Some(position) = position {
+                positions.push(position);
+            }
+        }
+
+        while positions.len() < count {
+            let Some(position) = self.random_position() else {
+                break;
+            };
+            positions.push(position);
+        }
+        positions
+    }
+}
+
+#[test]
+#[cfg(test)]
+fn tree_positions_test_1() {
+    let forest = Forest::new(&crate::dynamic_config::test_config());
+    let spacing = 40.0;
+
+    let trees = forest.tree_positions(100, spacing);
+
+    // Check resulting changes
+    assert!(!trees.is_empty());
+    for (i, tree) in trees.iter().enumerate() {
+        for other in trees[i + 1..].iter() {
+            assert!(tree.distance(*other) >= spacing);
+        }
+    }
+}
+
+#[test]
+#[cfg(test)]
+fn tree_positions_test_2() {
+    let forest = Forest::new(&crate::dynamic_config::test_config());
+
+    let trees = forest.tree_positions(100, 40.0);
+
+    // Check resulting changes
+    for tree in trees.iter() {
+        assert!(!forest.is_open(*tree));
+    }
+}
+
+#[test]
+#[cfg(test)]
+fn bush_positions_test_1() {
+    let config = crate::dynamic_config::test_config();
+    let forest = Forest::new(&config);
+    let trees = forest.tree_positions(50, 40.0);
+
+    let bushes = forest.bush_positions(200, &trees, &config);
+
+    // Check resulting changes
+    assert!(!bushes.is_empty());
+    for bush in bushes.iter() {
+        assert!(!forest.is_open(*bush));
+        assert!(forest.in_bounds(*bush));
+    }
+}
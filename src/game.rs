@@ -0,0 +1,234 @@
+use bevy::prelude::*;
+
+use crate::{
+    caterpillar::{self, CaterpillarHead},
+    dynamic_config::{DynamicConfig, GameMode},
+    foliage::Food,
+    menu::overlay_text,
+    predator::Fleeing,
+    simulation::SimulationTime,
+    social::Following,
+    toast::ToastEvent,
+    AppState,
+};
+
+/// How many leaves between "You ate ..." toasts.
+const SCORE_TOAST_INTERVAL: i32 = 10;
+
+/// Marks the caterpillar driven by the player in game mode.
+#[derive(Component)]
+pub struct PlayerCaterpillar;
+
+#[derive(Resource, Default)]
+pub struct Scoreboard {
+    pub score: i32,
+    pub time_remaining: f32,
+    pub result: Option<GameResult>,
+}
+
+pub enum GameResult {
+    Won,
+    TimeUp,
+    Beaten(String),
+    /// The player's caterpillar died before the game was decided.
+    Died,
+}
+
+#[derive(Component)]
+pub struct ScoreUi;
+
+#[derive(Component)]
+pub struct GameOverUi;
+
+pub fn setup_game(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    config: Res<DynamicConfig>,
+    mut ev_toast: EventWriter<ToastEvent>,
+) {
+    commands.insert_resource(Scoreboard {
+        time_remaining: config.game.duration,
+        ..default()
+    });
+
+    if config.game_mode != GameMode::Game {
+        return;
+    }
+
+    ev_toast.send(ToastEvent {
+        message: format!(
+            "Eat {} leaves in {} seconds. Steer with WASD.",
+            config.game.target_score, config.game.duration
+        ),
+        expiry_tick: 8000,
+    });
+
+    let regular_font: Handle<Font> = asset_server.load("fonts/FiraSans-Bold.ttf");
+    commands
+        .spawn(TextBundle {
+            style: Style {
+                align_self: AlignSelf::FlexEnd,
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Px(5.0),
+                    right: Val::Px(15.0),
+                    ..default()
+                },
+                ..default()
+            },
+            text: Text {
+                sections: vec![TextSection {
+                    value: "".to_string(),
+                    style: TextStyle {
+                        font: regular_font,
+                        font_size: 30.0,
+                        color: Color::WHITE,
+                    },
+                }],
+                alignment: Default::default(),
+            },
+            ..default()
+        })
+        .insert(ScoreUi);
+}
+
+/// Steers AI caterpillars towards the most appealing food they can see, weighing how nourishing
+/// and tasty it is against how far away it is.
+#[allow(clippy::type_complexity)]
+pub fn ai_seek_food_system(
+    config: Res<DynamicConfig>,
+    mut caterpillar_query: Query<
+        (&Transform, &mut CaterpillarHead),
+        (Without<Food>, Without<Fleeing>, Without<Following>),
+    >,
+    food_query: Query<(&Transform, &Food)>,
+) {
+    if config.game_mode != GameMode::Game {
+        return;
+    }
+
+    for (transform, mut caterpillar) in caterpillar_query.iter_mut() {
+        if caterpillar.manually_controlled {
+            continue;
+        }
+
+        let mut best: Option<Vec3> = None;
+        let mut best_appeal = 0.0;
+        for (food_transform, food) in food_query.iter() {
+            let distance = transform.translation.distance(food_transform.translation);
+            let preference = caterpillar.preference(food.kind);
+            if distance > config.game.ai_sight_radius || preference < config.diet.refuse_below {
+                continue;
+            }
+            let nutrition = config.foods[food.kind].nutrition;
+            let appeal = preference * nutrition / (distance + 1.0);
+            if appeal > best_appeal {
+                best_appeal = appeal;
+                best = Some(food_transform.translation);
+            }
+        }
+
+        if let Some(target) = best {
+            caterpillar.angle = caterpillar::heading_to(transform.translation, target);
+        }
+    }
+}
+
+pub fn game_rules_system(
+    time: Res<SimulationTime>,
+    config: Res<DynamicConfig>,
+    mut scoreboard: ResMut<Scoreboard>,
+    mut app_state: ResMut<State<AppState>>,
+    caterpillar_query: Query<(&CaterpillarHead, Option<&PlayerCaterpillar>)>,
+    mut ev_toast: EventWriter<ToastEvent>,
+) {
+    if config.game_mode != GameMode::Game {
+        return;
+    }
+
+    let mut player_alive = false;
+    for (caterpillar, player) in caterpillar_query.iter() {
+        if player.is_some() {
+            player_alive = true;
+            let previous_score = scoreboard.score;
+            scoreboard.score = caterpillar.food_eaten;
+            if scoreboard.score / SCORE_TOAST_INTERVAL > previous_score / SCORE_TOAST_INTERVAL {
+                ev_toast.send(ToastEvent {
+                    message: format!("You ate {} leaves", scoreboard.score),
+                    expiry_tick: 3000,
+                });
+            }
+            if scoreboard.score >= config.game.target_score && scoreboard.result.is_none() {
+                scoreboard.result = Some(GameResult::Won);
+            }
+        } else if caterpillar.food_eaten >= config.game.target_score && scoreboard.result.is_none()
+        {
+            scoreboard.result = Some(GameResult::Beaten(caterpillar.name.clone()));
+        }
+    }
+
+    if !player_alive && scoreboard.result.is_none() {
+        scoreboard.result = Some(GameResult::Died);
+    }
+
+    scoreboard.time_remaining -= time.delta_seconds();
+    if scoreboard.time_remaining <= 0.0 && scoreboard.result.is_none() {
+        scoreboard.time_remaining = 0.0;
+        scoreboard.result = Some(GameResult::TimeUp);
+    }
+
+    if scoreboard.result.is_some() {
+        // fails if a pause was queued this same frame, in which case the game ends on resuming.
+        let _ = app_state.set(AppState::GameOver);
+    }
+}
+
+pub fn update_score_ui_system(
+    config: Res<DynamicConfig>,
+    scoreboard: Res<Scoreboard>,
+    mut query: Query<&mut Text, With<ScoreUi>>,
+) {
+    for mut text in query.iter_mut() {
+        let seconds = scoreboard.time_remaining.ceil() as i32;
+        text.sections[0].value = format!(
+            "Leaves {}/{}   {}:{:02}",
+            scoreboard.score,
+            config.game.target_score,
+            seconds / 60,
+            seconds % 60
+        );
+    }
+}
+
+pub fn setup_game_over(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    scoreboard: Res<Scoreboard>,
+) {
+    let message = match &scoreboard.result {
+        Some(GameResult::Won) => format!(
+            "You win!\n\nYou ate {} leaves with {:.0} seconds to spare.",
+            scoreboard.score, scoreboard.time_remaining
+        ),
+        Some(GameResult::Beaten(name)) => format!(
+            "You lose!\n\n{} ate their fill first. You ate {} leaves.",
+            name, scoreboard.score
+        ),
+        Some(GameResult::Died) => format!(
+            "You lose!\n\nYour caterpillar died. You ate {} leaves.",
+            scoreboard.score
+        ),
+        _ => format!("Time's up!\n\nYou ate {} leaves.", scoreboard.score),
+    };
+    let message = format!("{}\n\nPress Enter to return to the menu.", message);
+    commands
+        .spawn(overlay_text(&asset_server, message, 40.0))
+        .insert(GameOverUi);
+}
+
+pub fn game_over_system(mut keys: ResMut<Input<KeyCode>>, mut app_state: ResMut<State<AppState>>) {
+    if keys.just_pressed(KeyCode::Return) {
+        keys.reset(KeyCode::Return);
+        app_state.set(AppState::Menu).unwrap();
+    }
+}
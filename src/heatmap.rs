@@ -0,0 +1,177 @@
+use bevy::{
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+
+use crate::{
+    caterpillar::CaterpillarHead, dynamic_config::DynamicConfig, foliage::FoodEatenEvent,
+    simulation::SimulationTime,
+};
+
+/// Height of the overlay above the ground, so it isn't hidden by it.
+const OVERLAY_HEIGHT: f32 = 0.2;
+
+/// Seconds between redraws of the overlay texture.
+const REFRESH_SECONDS: f32 = 0.5;
+
+const VISIT_COLOUR: [f32; 3] = [1.0, 0.1, 0.0];
+const EATEN_COLOUR: [f32; 3] = [1.0, 1.0, 0.2];
+
+#[derive(Component)]
+pub struct HeatmapOverlay;
+
+/// Where caterpillars have spent their time and eaten, over the whole run.
+#[derive(Resource)]
+pub struct Heatmap {
+    resolution: usize,
+    /// Simulated seconds caterpillar heads have spent in each cell.
+    visits: Vec<f32>,
+    /// Bites taken out of bushes in each cell.
+    eaten: Vec<f32>,
+    image_handle: Handle<Image>,
+    refresh_timer: Timer,
+}
+
+impl Heatmap {
+    /// Finds the cell under a world position. Row 0 is the far (+Z) edge, to match the
+    /// plane's texture coordinates.
+    fn cell(&self, translation: Vec3, plane_size: f32) -> Option<usize> {
+        let u = translation.x / plane_size + 0.5;
+        let v = 0.5 - translation.z / plane_size;
+        if !(0.0..1.0).contains(&u) || !(0.0..1.0).contains(&v) {
+            return None;
+        }
+        let x = (u * self.resolution as f32) as usize;
+        let y = (v * self.resolution as f32) as usize;
+        Some(y * self.resolution + x)
+    }
+}
+
+pub fn setup_heatmap(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut images: ResMut<Assets<Image>>,
+    config: Res<DynamicConfig>,
+) {
+    let resolution = config.heatmap.resolution.max(1);
+    let image = Image::new_fill(
+        Extent3d {
+            width: resolution as u32,
+            height: resolution as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[0, 0, 0, 0],
+        TextureFormat::Rgba8UnormSrgb,
+    );
+    let image_handle = images.add(image);
+
+    commands
+        .spawn(PbrBundle {
+            mesh: meshes.add(Mesh::from(shape::Plane {
+                size: config.plane_size,
+            })),
+            material: materials.add(StandardMaterial {
+                base_color_texture: Some(image_handle.clone()),
+                unlit: true,
+                alpha_mode: AlphaMode::Blend,
+                ..default()
+            }),
+            transform: Transform::from_xyz(0.0, OVERLAY_HEIGHT, 0.0),
+            visibility: Visibility {
+                is_visible: config.heatmap.enabled,
+            },
+            ..default()
+        })
+        .insert(HeatmapOverlay);
+
+    commands.insert_resource(Heatmap {
+        resolution,
+        visits: vec![0.0; resolution * resolution],
+        eaten: vec![0.0; resolution * resolution],
+        image_handle,
+        refresh_timer: Timer::from_seconds(REFRESH_SECONDS, TimerMode::Repeating),
+    });
+}
+
+pub fn record_heatmap_system(
+    simulation_time: Res<SimulationTime>,
+    config: Res<DynamicConfig>,
+    mut heatmap: ResMut<Heatmap>,
+    mut ev_eaten: EventReader<FoodEatenEvent>,
+    head_query: Query<&Transform, With<CaterpillarHead>>,
+) {
+    let delta = simulation_time.delta_seconds();
+    if delta > 0.0 {
+        for transform in head_query.iter() {
+            if let Some(cell) = heatmap.cell(transform.translation, config.plane_size) {
+                heatmap.visits[cell] += delta;
+            }
+        }
+    }
+
+    for ev in ev_eaten.iter() {
+        if let Some(cell) = heatmap.cell(ev.position, config.plane_size) {
+            heatmap.eaten[cell] += 1.0;
+        }
+    }
+}
+
+/// Redraws the overlay texture from the totals. Visits are shown in red, growing more opaque
+/// with time spent there, and eaten bushes in yellow on top.
+pub fn update_heatmap_image_system(
+    time: Res<Time>,
+    mut heatmap: ResMut<Heatmap>,
+    mut images: ResMut<Assets<Image>>,
+    overlay_query: Query<&Visibility, With<HeatmapOverlay>>,
+) {
+    if !heatmap.refresh_timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    // no need to redraw something nobody can see.
+    if !overlay_query.iter().any(|visibility| visibility.is_visible) {
+        return;
+    }
+    let Some(image) = images.get_mut(&heatmap.image_handle) else {
+        return;
+    };
+
+    // a log scale stops a few busy cells from washing out everything else.
+    let max_visits = heatmap.visits.iter().copied().fold(0.0, f32::max).ln_1p();
+    let max_eaten = heatmap.eaten.iter().copied().fold(0.0, f32::max).ln_1p();
+    let scale = |value: f32, max: f32| {
+        if max > 0.0 {
+            value.ln_1p() / max
+        } else {
+            0.0
+        }
+    };
+
+    for (i, pixel) in image.data.chunks_exact_mut(4).enumerate() {
+        let visits = scale(heatmap.visits[i], max_visits);
+        let eaten = scale(heatmap.eaten[i], max_eaten);
+        let colour = if eaten > 0.0 {
+            EATEN_COLOUR
+        } else {
+            VISIT_COLOUR
+        };
+        let alpha = (visits * 0.6).max(eaten * 0.9);
+        pixel[0] = (colour[0] * 255.0) as u8;
+        pixel[1] = (colour[1] * 255.0) as u8;
+        pixel[2] = (colour[2] * 255.0) as u8;
+        pixel[3] = (alpha * 255.0) as u8;
+    }
+}
+
+/// H shows and hides the heatmap.
+pub fn toggle_heatmap_system(
+    keys: Res<Input<KeyCode>>,
+    mut overlay_query: Query<&mut Visibility, With<HeatmapOverlay>>,
+) {
+    if keys.just_pressed(KeyCode::H) {
+        for mut visibility in overlay_query.iter_mut() {
+            visibility.is_visible = !visibility.is_visible;
+        }
+    }
+}
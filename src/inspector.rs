@@ -0,0 +1,297 @@
+use bevy::{prelude::*, window::ReceivedCharacter};
+
+use crate::{
+    caterpillar::{self, CaterpillarAssets, CaterpillarHead, CaterpillarPart, CaterpillarSpawn},
+    dynamic_config::DynamicConfig,
+    random,
+    simulation::SimulationTime,
+    ui::SelectedCaterpillar,
+};
+
+const BUTTON_COLOUR: Color = Color::rgba(0.0, 0.0, 0.0, 0.5);
+const BUTTON_HOVER_COLOUR: Color = Color::rgba(0.2, 0.2, 0.2, 0.7);
+
+/// Multiplier applied by the faster and slower buttons.
+const SPEED_CHANGE: f32 = 1.25;
+
+/// Longest name that can be typed in.
+const MAX_NAME_LENGTH: usize = 24;
+
+#[derive(Component)]
+pub struct InspectorUi;
+
+#[derive(Component)]
+pub struct InspectorStatsUi;
+
+#[derive(Component, Clone, Copy)]
+pub enum InspectorButton {
+    Slower,
+    Faster,
+    Rename,
+    Clone,
+    Kill,
+}
+
+impl InspectorButton {
+    fn label(&self) -> &'static str {
+        match self {
+            InspectorButton::Slower => "Slower",
+            InspectorButton::Faster => "Faster",
+            InspectorButton::Rename => "Rename",
+            InspectorButton::Clone => "Clone",
+            InspectorButton::Kill => "Kill",
+        }
+    }
+}
+
+/// The caterpillar being renamed and the name typed so far.
+#[derive(Resource, Default)]
+pub struct RenameState {
+    pub target: Option<Entity>,
+    pub buffer: String,
+}
+
+pub fn setup_inspector(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let mono_font = asset_server.load("fonts/FiraMono-Regular.ttf");
+    let regular_font: Handle<Font> = asset_server.load("fonts/FiraSans-Bold.ttf");
+
+    commands.insert_resource(RenameState::default());
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Px(360.0),
+                    left: Val::Px(15.0),
+                    ..default()
+                },
+                flex_direction: FlexDirection::Column,
+                display: Display::None,
+                ..default()
+            },
+            ..default()
+        })
+        .insert(InspectorUi)
+        .with_children(|parent| {
+            parent
+                .spawn(TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: mono_font,
+                        font_size: 16.0,
+                        color: Color::WHITE,
+                    },
+                ))
+                .insert(InspectorStatsUi);
+
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Row,
+                        margin: UiRect {
+                            top: Val::Px(8.0),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|row| {
+                    for button in [
+                        InspectorButton::Slower,
+                        InspectorButton::Faster,
+                        InspectorButton::Rename,
+                        InspectorButton::Clone,
+                        InspectorButton::Kill,
+                    ] {
+                        row.spawn(ButtonBundle {
+                            style: Style {
+                                padding: UiRect::all(Val::Px(6.0)),
+                                margin: UiRect::all(Val::Px(2.0)),
+                                ..default()
+                            },
+                            background_color: BUTTON_COLOUR.into(),
+                            ..default()
+                        })
+                        .insert(button)
+                        .with_children(|button_parent| {
+                            button_parent.spawn(TextBundle::from_section(
+                                button.label(),
+                                TextStyle {
+                                    font: regular_font.clone(),
+                                    font_size: 16.0,
+                                    color: Color::WHITE,
+                                },
+                            ));
+                        });
+                    }
+                });
+        });
+}
+
+/// Converts a forward vector into a compass bearing in degrees, with -Z as north.
+pub fn heading_degrees(forward: Vec3) -> f32 {
+    let degrees = f32::atan2(forward.x, -forward.z).to_degrees();
+    if degrees < 0.0 {
+        degrees + 360.0
+    } else {
+        degrees
+    }
+}
+
+pub fn update_inspector_system(
+    simulation_time: Res<SimulationTime>,
+    config: Res<DynamicConfig>,
+    rename_state: Res<RenameState>,
+    selected_query: Query<(&CaterpillarHead, &Transform), With<SelectedCaterpillar>>,
+    part_query: Query<&CaterpillarPart>,
+    mut inspector_query: Query<&mut Style, With<InspectorUi>>,
+    mut stats_query: Query<&mut Text, With<InspectorStatsUi>>,
+) {
+    let selected = selected_query.get_single().ok();
+
+    for mut style in inspector_query.iter_mut() {
+        style.display = if selected.is_some() {
+            Display::Flex
+        } else {
+            Display::None
+        };
+    }
+
+    if let Some((caterpillar, transform)) = selected {
+        for mut text in stats_query.iter_mut() {
+            let mut stats = format!(
+                "Speed     {:.1}\nLength    {} segments\nHeading   {:.0}°\nTravelled {:.0}\nEaten     {}\nNourished {:.1}\nFavourite {}\nAge       {:.0}s",
+                caterpillar.speed,
+                caterpillar::caterpillar_length(caterpillar, &part_query),
+                heading_degrees(transform.forward()),
+                caterpillar.distance_travelled,
+                caterpillar.food_eaten,
+                caterpillar.nourishment,
+                caterpillar
+                    .favourite_food()
+                    .and_then(|kind| config.foods.get(kind))
+                    .map_or("-", |food_type| food_type.name.as_str()),
+                simulation_time.elapsed_seconds() - caterpillar.born,
+            );
+            if rename_state.target.is_some() {
+                stats = format!(
+                    "{}\n\nNew name: {}_\n(Enter to confirm, Esc to cancel)",
+                    stats, rename_state.buffer
+                );
+            }
+            text.sections[0].value = stats;
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn inspector_button_system(
+    mut commands: Commands,
+    simulation_time: Res<SimulationTime>,
+    caterpillar_assets: Res<CaterpillarAssets>,
+    config: Res<DynamicConfig>,
+    mut rename_state: ResMut<RenameState>,
+    mut button_query: Query<
+        (&Interaction, &mut BackgroundColor, &InspectorButton),
+        Changed<Interaction>,
+    >,
+    mut selected_query: Query<
+        (Entity, &mut CaterpillarHead, &Transform),
+        With<SelectedCaterpillar>,
+    >,
+    part_query: Query<&CaterpillarPart>,
+) {
+    for (interaction, mut colour, button) in button_query.iter_mut() {
+        match *interaction {
+            Interaction::Hovered => *colour = BUTTON_HOVER_COLOUR.into(),
+            Interaction::None => *colour = BUTTON_COLOUR.into(),
+            Interaction::Clicked => {
+                let Ok((entity, mut caterpillar, transform)) = selected_query.get_single_mut()
+                else {
+                    continue;
+                };
+                match button {
+                    InspectorButton::Slower => caterpillar.speed /= SPEED_CHANGE,
+                    InspectorButton::Faster => caterpillar.speed *= SPEED_CHANGE,
+                    InspectorButton::Rename => {
+                        rename_state.target = Some(entity);
+                        rename_state.buffer.clear();
+                    }
+                    InspectorButton::Clone => {
+                        caterpillar::spawn_caterpillar(
+                            &mut commands,
+                            &caterpillar_assets,
+                            CaterpillarSpawn {
+                                name: format!("{} Jr.", caterpillar.name),
+                                description: random::from_vec(&config.child_thoughts),
+                                speed: caterpillar.speed,
+                                length: caterpillar::caterpillar_length(&caterpillar, &part_query),
+                                translation: transform.translation,
+                                manually_controlled: false,
+                                diet: caterpillar.diet.clone(),
+                                palette: caterpillar.palette,
+                            },
+                            simulation_time.elapsed_seconds(),
+                        );
+                    }
+                    InspectorButton::Kill => {
+                        caterpillar::despawn_caterpillar(
+                            &mut commands,
+                            entity,
+                            &caterpillar,
+                            &part_query,
+                        );
+                        rename_state.target = None;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Captures typing while a caterpillar is being renamed. Runs straight after input is
+/// collected and swallows the keys pressed while typing so hotkeys don't fire. Keys already held,
+/// such as the player steering, are left alone.
+pub fn rename_input_system(
+    mut keys: ResMut<Input<KeyCode>>,
+    mut ev_characters: EventReader<ReceivedCharacter>,
+    rename_state: Option<ResMut<RenameState>>,
+    mut caterpillar_query: Query<&mut CaterpillarHead>,
+) {
+    let Some(mut rename_state) = rename_state else {
+        return;
+    };
+    let Some(target) = rename_state.target else {
+        ev_characters.clear();
+        return;
+    };
+
+    for ev in ev_characters.iter() {
+        if !ev.char.is_control() && rename_state.buffer.chars().count() < MAX_NAME_LENGTH {
+            rename_state.buffer.push(ev.char);
+        }
+    }
+
+    if keys.just_pressed(KeyCode::Back) {
+        rename_state.buffer.pop();
+    }
+    if keys.just_pressed(KeyCode::Return) {
+        let name = rename_state.buffer.trim().to_string();
+        if !name.is_empty() {
+            if let Ok(mut caterpillar) = caterpillar_query.get_mut(target) {
+                caterpillar.name = name;
+            }
+        }
+        rename_state.target = None;
+    }
+    if keys.just_pressed(KeyCode::Escape) {
+        rename_state.target = None;
+    }
+
+    let typed: Vec<KeyCode> = keys.get_just_pressed().copied().collect();
+    for key in typed {
+        keys.reset(key);
+    }
+}
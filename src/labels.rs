@@ -0,0 +1,171 @@
+use bevy::{prelude::*, ui::FocusPolicy};
+
+use crate::{
+    camera::{self, PanOrbitCamera},
+    caterpillar::CaterpillarHead,
+    dynamic_config::DynamicConfig,
+    ui::SelectedCaterpillar,
+};
+
+/// Depth below the middle of a caterpillar's head that its name hangs from, leaving the space
+/// above for its thought bubble.
+const LABEL_DEPTH: f32 = 3.0;
+
+const LABEL_COLOUR: Color = Color::WHITE;
+const SELECTED_LABEL_COLOUR: Color = Color::rgb(1.0, 1.0, 0.2);
+
+/// How much bigger the selected caterpillar's name is drawn.
+const SELECTED_LABEL_SCALE: f32 = 1.25;
+
+/// Covers the screen and holds every name label, so L can show and hide them all at once.
+#[derive(Component)]
+pub struct NameLabelLayer;
+
+/// Text showing the name of the caterpillar it follows.
+#[derive(Component)]
+pub struct NameLabel {
+    target: Entity,
+}
+
+pub fn setup_name_labels(mut commands: Commands, config: Res<DynamicConfig>) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                ..default()
+            },
+            focus_policy: FocusPolicy::Pass,
+            visibility: Visibility {
+                is_visible: config.name_labels.enabled,
+            },
+            ..default()
+        })
+        .insert(NameLabelLayer);
+}
+
+/// L shows and hides name labels.
+pub fn toggle_name_labels_system(
+    keys: Res<Input<KeyCode>>,
+    mut layer_query: Query<&mut Visibility, With<NameLabelLayer>>,
+) {
+    if keys.just_pressed(KeyCode::L) {
+        for mut visibility in layer_query.iter_mut() {
+            visibility.is_visible = !visibility.is_visible;
+        }
+    }
+}
+
+/// Keeps each caterpillar's name under it on screen, shrinking with distance and hidden when
+/// far away or off screen. The selected caterpillar's name is picked out in yellow.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn name_label_system(
+    mut commands: Commands,
+    config: Res<DynamicConfig>,
+    asset_server: Res<AssetServer>,
+    layer_query: Query<(Entity, &Visibility), (With<NameLabelLayer>, Without<NameLabel>)>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<PanOrbitCamera>>,
+    new_caterpillar_query: Query<Entity, Added<CaterpillarHead>>,
+    caterpillar_query: Query<(
+        &GlobalTransform,
+        &CaterpillarHead,
+        Option<&SelectedCaterpillar>,
+    )>,
+    mut label_query: Query<(
+        Entity,
+        &NameLabel,
+        &mut Text,
+        &mut Style,
+        &mut Visibility,
+        &Node,
+    )>,
+) {
+    let Ok((layer, layer_visibility)) = layer_query.get_single() else {
+        return;
+    };
+    let label_config = &config.name_labels;
+
+    if !new_caterpillar_query.is_empty() {
+        let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+        commands.entity(layer).with_children(|parent| {
+            for entity in new_caterpillar_query.iter() {
+                parent
+                    .spawn(
+                        TextBundle::from_section(
+                            "",
+                            TextStyle {
+                                font: font.clone(),
+                                font_size: label_config.font_size,
+                                color: LABEL_COLOUR,
+                            },
+                        )
+                        .with_style(Style {
+                            position_type: PositionType::Absolute,
+                            ..default()
+                        }),
+                    )
+                    .insert(NameLabel { target: entity });
+            }
+        });
+    }
+
+    let camera = camera_query.iter().next();
+    for (label_entity, label, mut text, mut style, mut visibility, node) in label_query.iter_mut() {
+        let Ok((transform, caterpillar, selected)) = caterpillar_query.get(label.target) else {
+            commands.entity(label_entity).despawn_recursive();
+            continue;
+        };
+        // nothing to keep up to date while the labels are hidden.
+        if !layer_visibility.is_visible {
+            continue;
+        }
+
+        let anchor = transform.translation() - Vec3::Y * LABEL_DEPTH;
+        let placement = camera.and_then(|(camera, camera_transform)| {
+            let distance = camera_transform.translation().distance(anchor);
+            if distance > label_config.max_distance {
+                return None;
+            }
+            camera::world_to_ui(camera, camera_transform, anchor)
+                .map(|screen_position| (screen_position, distance))
+        });
+
+        let shown = placement.is_some();
+        if visibility.is_visible != shown {
+            visibility.is_visible = shown;
+        }
+        let Some((screen_position, distance)) = placement else {
+            continue;
+        };
+
+        let mut scale = (label_config.full_size_distance / distance.max(0.01))
+            .clamp(label_config.min_scale.min(1.0), 1.0);
+        let colour = if selected.is_some() {
+            scale *= SELECTED_LABEL_SCALE;
+            SELECTED_LABEL_COLOUR
+        } else {
+            LABEL_COLOUR
+        };
+        // whole sizes only, so the text isn't laid out again every time the camera moves.
+        let font_size = (label_config.font_size * scale).round();
+
+        let section = &text.sections[0];
+        if section.value != caterpillar.name
+            || section.style.font_size != font_size
+            || section.style.color != colour
+        {
+            let section = &mut text.sections[0];
+            section.value = caterpillar.name.clone();
+            section.style.font_size = font_size;
+            section.style.color = colour;
+        }
+
+        // the name hangs below the head, centred on it.
+        let size = node.size();
+        style.position = UiRect {
+            left: Val::Px(screen_position.x - size.x / 2.0),
+            top: Val::Px(screen_position.y),
+            ..default()
+        };
+    }
+}
@@ -0,0 +1,141 @@
+use bevy::{ecs::system::EntityCommands, prelude::*};
+
+use crate::{
+    camera::PanOrbitCamera,
+    dynamic_config::{DynamicConfig, RenderTechnique},
+};
+
+/// How close the camera has to turn before a billboard is turned to face it again, in radians.
+const BILLBOARD_TURN: f32 = 0.05;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum LodLevel {
+    High,
+    Low,
+    Billboard,
+    Hidden,
+}
+
+/// The meshes one kind of object can be drawn with, and how the config says to pick between them.
+#[derive(Clone)]
+pub struct LodMeshes {
+    technique: RenderTechnique,
+    high: Handle<Mesh>,
+    low: Handle<Mesh>,
+    billboard: Option<Handle<Mesh>>,
+}
+
+impl LodMeshes {
+    /// Only adds the meshes the technique will actually use. The billboard should only be given
+    /// for things that look much the same from every side, as it's turned to face the camera.
+    pub fn new(
+        meshes: &mut Assets<Mesh>,
+        technique: RenderTechnique,
+        high: Mesh,
+        low: Mesh,
+        billboard: Option<Mesh>,
+    ) -> Self {
+        let (high, low) = match technique {
+            RenderTechnique::Full => {
+                let high = meshes.add(high);
+                (high.clone(), high)
+            }
+            RenderTechnique::LowPoly => {
+                let low = meshes.add(low);
+                (low.clone(), low)
+            }
+            RenderTechnique::Lod => (meshes.add(high), meshes.add(low)),
+        };
+        let billboard = billboard
+            .filter(|_| technique == RenderTechnique::Lod)
+            .map(|mesh| meshes.add(mesh));
+        LodMeshes {
+            technique,
+            high,
+            low,
+            billboard,
+        }
+    }
+
+    /// The mesh to spawn with.
+    pub fn mesh(&self) -> Handle<Mesh> {
+        self.high.clone()
+    }
+
+    /// Adds a `Lod` to the entity if its mesh should change with distance.
+    pub fn insert_lod(&self, entity: &mut EntityCommands) {
+        if self.technique == RenderTechnique::Lod {
+            entity.insert(Lod {
+                meshes: self.clone(),
+                level: LodLevel::High,
+                yaw: 0.0,
+            });
+        }
+    }
+}
+
+/// Swaps an entity's mesh for a simpler one the further it is from the camera.
+#[derive(Component)]
+pub struct Lod {
+    meshes: LodMeshes,
+    level: LodLevel,
+    /// Which way the billboard was last turned.
+    yaw: f32,
+}
+
+/// Picks each entity's mesh by its distance from the camera, and turns billboards to face it.
+pub fn lod_system(
+    config: Res<DynamicConfig>,
+    camera_query: Query<&GlobalTransform, With<PanOrbitCamera>>,
+    mut lod_query: Query<(
+        &GlobalTransform,
+        &mut Transform,
+        &mut Lod,
+        &mut Handle<Mesh>,
+        &mut Visibility,
+    )>,
+) {
+    let Some(camera_transform) = camera_query.iter().next() else {
+        return;
+    };
+    let camera = camera_transform.translation();
+    let rendering = &config.rendering;
+
+    for (global_transform, mut transform, mut lod, mut mesh, mut visibility) in lod_query.iter_mut()
+    {
+        let offset = camera - global_transform.translation();
+        let distance = offset.length();
+        let level = if rendering.cull_distance > 0.0 && distance > rendering.cull_distance {
+            LodLevel::Hidden
+        } else if distance > rendering.billboard_distance && lod.meshes.billboard.is_some() {
+            LodLevel::Billboard
+        } else if distance > rendering.low_detail_distance {
+            LodLevel::Low
+        } else {
+            LodLevel::High
+        };
+
+        if level != lod.level {
+            let handle = match level {
+                LodLevel::High => Some(&lod.meshes.high),
+                LodLevel::Low => Some(&lod.meshes.low),
+                LodLevel::Billboard => lod.meshes.billboard.as_ref(),
+                LodLevel::Hidden => None,
+            };
+            if let Some(handle) = handle {
+                *mesh = handle.clone();
+            }
+            visibility.is_visible = level != LodLevel::Hidden;
+            lod.level = level;
+        }
+
+        if level == LodLevel::Billboard {
+            // billboards face +Z before they're turned.
+            let yaw = f32::atan2(offset.x, offset.z);
+            if (yaw - lod.yaw).abs() > BILLBOARD_TURN {
+                transform.rotation = Quat::from_rotation_y(yaw);
+                lod.yaw = yaw;
+            }
+        }
+    }
+}
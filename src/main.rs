@@ -1,42 +1,70 @@
 mod camera;
+mod capture;
 mod caterpillar;
+mod climbing;
 mod collision;
 mod config;
+mod day_night;
 mod dynamic_config;
 mod foliage;
+mod forest;
+mod game;
+mod heatmap;
+mod inspector;
+mod labels;
+mod lod;
+mod menu;
+mod minimap;
 mod pick_events;
+mod predator;
 mod random;
+mod replay;
+mod simulation;
+mod social;
+mod speech;
+mod thoughts;
 mod toast;
+mod trail;
 mod ui;
+mod weather;
 
 use std::cell::RefCell;
 
-use bevy::{diagnostic::FrameTimeDiagnosticsPlugin, prelude::*, time::FixedTimestep};
+use bevy::{diagnostic::FrameTimeDiagnosticsPlugin, prelude::*, transform::TransformSystem};
 use bevy_common_assets::json::JsonAssetPlugin;
 use bevy_mod_picking::*;
 use bevy_tweening::*;
-use camera::FollowCamera;
+use camera::{CameraModeState, FollowCamera};
 use dynamic_config::DynamicConfig;
+use simulation::SimulationTime;
 use toast::ToastEvent;
 use wasm_bindgen::prelude::*;
 
-const TIMESTEP_1_PER_SECOND: f64 = 1.0;
-
 #[derive(Clone, Eq, PartialEq, Debug, Hash)]
 pub enum AppState {
     Boot,
+    Menu,
     ConfigLoad,
     PreLoad,
     Loading,
-    Level,
+    Playing,
+    Paused,
+    GameOver,
+    ReplayLoad,
+    Replay,
 }
 
 fn main() {
     App::new()
         .add_state(AppState::Boot)
         .add_event::<ToastEvent>()
-        .insert_resource(ClearColor(Color::rgb(0.53, 0.80, 0.92)))
+        .add_event::<foliage::FoodEatenEvent>()
+        .insert_resource(ClearColor(day_night::DAY_SKY_COLOUR))
         .insert_resource(FollowCamera::default())
+        .insert_resource(SimulationTime::default())
+        .init_resource::<CameraModeState>()
+        .init_resource::<pick_events::GroundClick>()
+        .init_resource::<pick_events::HoveredCaterpillar>()
         .add_plugins(DefaultPlugins.set(WindowPlugin {
             window: WindowDescriptor {
                 width: config::START_RESOLUTION_WIDTH,
@@ -50,11 +78,43 @@ fn main() {
         .add_plugin(PickingPlugin)
         .add_plugin(InteractablePickingPlugin)
         .add_plugin(JsonAssetPlugin::<DynamicConfig>::new(&["json"]))
-        .add_system(bevy::window::close_on_esc)
+        .add_plugin(capture::CapturePlugin)
         .add_system(toast::toast_system)
+        .add_system(window_resize_system)
+        .add_system(capture::save_captures_system)
+        .add_system_to_stage(
+            CoreStage::PostUpdate,
+            capture::capture_system.before(TransformSystem::TransformPropagate),
+        )
+        .add_system_to_stage(
+            CoreStage::PreUpdate,
+            inspector::rename_input_system.after(bevy::input::InputSystem),
+        )
+        .add_system_to_stage(
+            CoreStage::PreUpdate,
+            simulation::time_controls_system
+                .after(inspector::rename_input_system)
+                .before(simulation::update_simulation_time_system),
+        )
+        .add_system_to_stage(
+            CoreStage::PreUpdate,
+            simulation::update_simulation_time_system,
+        )
+        .add_system_to_stage(
+            CoreStage::PreUpdate,
+            simulation::sync_animation_speed_system
+                .after(simulation::update_simulation_time_system),
+        )
         .add_system_set(
             SystemSet::on_update(AppState::Boot).with_system(dynamic_config::create_dynamic_config),
         )
+        .add_system_set(
+            SystemSet::on_enter(AppState::Menu)
+                .with_system(teardown_level)
+                .with_system(menu::setup_menu),
+        )
+        .add_system_set(SystemSet::on_update(AppState::Menu).with_system(menu::menu_system))
+        .add_system_set(SystemSet::on_exit(AppState::Menu).with_system(menu::teardown_menu))
         .add_system_set(
             SystemSet::on_update(AppState::ConfigLoad)
                 .with_system(dynamic_config::load_dynamic_config),
@@ -62,6 +122,7 @@ fn main() {
         .add_system_set(
             SystemSet::on_update(AppState::PreLoad)
                 .with_system(foliage::setup_foliage_assets)
+                .with_system(caterpillar::setup_caterpillar_assets)
                 .with_system(preloading_completed),
         )
         .add_system_set(
@@ -70,25 +131,124 @@ fn main() {
                 .with_system(foliage::setup_foliage)
                 .with_system(caterpillar::setup_caterpillars)
                 .with_system(camera::spawn_camera)
-                .with_system(ui::infotext_system),
+                .with_system(ui::infotext_system)
+                .with_system(inspector::setup_inspector)
+                .with_system(minimap::setup_minimap)
+                .with_system(capture::setup_capture)
+                .with_system(replay::setup_replay_recorder)
+                .with_system(trail::setup_trails)
+                .with_system(heatmap::setup_heatmap)
+                .with_system(day_night::setup_day_night)
+                .with_system(weather::setup_weather)
+                .with_system(predator::setup_predators)
+                .with_system(speech::setup_speech_bubbles)
+                .with_system(labels::setup_name_labels)
+                .with_system(game::setup_game),
         )
         .add_system_set(SystemSet::on_update(AppState::Loading).with_system(loading_completed))
         .add_system_set(
-            SystemSet::on_update(AppState::Level)
+            SystemSet::on_update(AppState::Playing)
                 .with_system(caterpillar::caterpillar_system)
                 .with_system(caterpillar::camera_follow_caterpillar_system)
                 .with_system(camera::pan_orbit_camera)
+                .with_system(camera::camera_mode_input_system)
+                .with_system(camera::free_fly_camera_system)
+                .with_system(camera::camera_mode_system)
                 .with_system(ui::change_text_system)
                 .with_system(ui::update_flavour_text_system)
                 .with_system(ui::update_debug_ui_system)
+                .with_system(ui::update_speed_ui_system)
+                .with_system(inspector::update_inspector_system)
+                .with_system(inspector::inspector_button_system)
                 .with_system(pick_events::print_events)
-                .with_system(window_resize_system)
-                .with_system(caterpillar::eat_check),
+                .with_system(pick_events::deselect_system)
+                .with_system(pick_events::hover_highlight_system)
+                .with_system(minimap::toggle_minimap_system)
+                .with_system(minimap::minimap_density_system)
+                .with_system(minimap::minimap_marker_system)
+                .with_system(minimap::minimap_click_system)
+                .with_system(capture::capture_request_system)
+                .with_system(replay::record_foliage_system)
+                .with_system(replay::record_replay_system)
+                .with_system(trail::spawn_trails_system)
+                .with_system(trail::update_trails_system)
+                .with_system(trail::toggle_trails_system)
+                .with_system(heatmap::record_heatmap_system)
+                .with_system(heatmap::update_heatmap_image_system)
+                .with_system(heatmap::toggle_heatmap_system)
+                .with_system(day_night::day_night_system)
+                .with_system(day_night::sleep_system)
+                .with_system(weather::weather_system)
+                .with_system(weather::rain_system)
+                .with_system(weather::wind_system)
+                .with_system(caterpillar::eat_check)
+                .with_system(foliage::spawn_bushes)
+                .with_system(foliage::bush_growth_system)
+                .with_system(climbing::setup_canopy_food_system)
+                .with_system(climbing::start_climb_system)
+                .with_system(climbing::climb_system)
+                .with_system(climbing::canopy_growth_system)
+                .with_system(lod::lod_system)
+                .with_system(predator::predator_system)
+                .with_system(predator::flee_system)
+                .with_system(predator::wing_system)
+                .with_system(social::procession_system)
+                .with_system(social::crowd_system.after(game::ai_seek_food_system))
+                .with_system(social::competition_system)
+                .with_system(thoughts::thought_system)
+                .with_system(speech::speech_bubble_system)
+                .with_system(speech::toggle_speech_bubbles_system)
+                .with_system(labels::name_label_system)
+                .with_system(labels::toggle_name_labels_system)
+                .with_system(game::ai_seek_food_system)
+                .with_system(game::game_rules_system)
+                .with_system(game::update_score_ui_system)
+                .with_system(menu::pause_system),
+        )
+        .add_system_set(
+            SystemSet::on_exit(AppState::Playing).with_system(replay::finish_replay_recording),
         )
         .add_system_set(
-            SystemSet::on_update(AppState::Level)
-                .with_run_criteria(FixedTimestep::step(TIMESTEP_1_PER_SECOND))
-                .with_system(foliage::spawn_bushes),
+            SystemSet::on_pause(AppState::Playing)
+                .with_system(caterpillar::pause_leg_animation_system),
+        )
+        .add_system_set(
+            SystemSet::on_resume(AppState::Playing)
+                .with_system(caterpillar::resume_leg_animation_system),
+        )
+        .add_system_set(SystemSet::on_enter(AppState::Paused).with_system(menu::setup_paused))
+        .add_system_set(
+            SystemSet::on_update(AppState::Paused)
+                .with_system(menu::pause_system)
+                .with_system(menu::return_to_menu_system),
+        )
+        .add_system_set(SystemSet::on_exit(AppState::Paused).with_system(menu::teardown_paused))
+        .add_system_set(SystemSet::on_enter(AppState::GameOver).with_system(game::setup_game_over))
+        .add_system_set(
+            SystemSet::on_update(AppState::GameOver).with_system(game::game_over_system),
+        )
+        .add_system_set(
+            SystemSet::on_enter(AppState::ReplayLoad).with_system(replay::start_replay_loading),
+        )
+        .add_system_set(
+            SystemSet::on_update(AppState::ReplayLoad)
+                .with_system(foliage::setup_foliage_assets)
+                .with_system(caterpillar::setup_caterpillar_assets)
+                .with_system(replay::replay_loading_completed),
+        )
+        .add_system_set(
+            SystemSet::on_enter(AppState::Replay)
+                .with_system(setup_scene)
+                .with_system(camera::spawn_camera)
+                .with_system(replay::setup_replay),
+        )
+        .add_system_set(
+            SystemSet::on_update(AppState::Replay)
+                .with_system(camera::pan_orbit_camera)
+                .with_system(replay::replay_controls_system)
+                .with_system(replay::replay_playback_system)
+                .with_system(replay::update_replay_ui_system)
+                .with_system(lod::lod_system),
         )
         .run();
 }
@@ -150,7 +310,37 @@ fn preloading_completed(mut app_state: ResMut<State<AppState>>) {
 }
 
 fn loading_completed(mut app_state: ResMut<State<AppState>>) {
-    app_state.set(AppState::Level).unwrap();
+    app_state.set(AppState::Playing).unwrap();
+}
+
+/// Clears out everything the level spawned so another config can be loaded from the menu.
+fn teardown_level(mut commands: Commands, root_query: Query<Entity, Without<Parent>>) {
+    // every entity belongs to the level at this point, the menu spawns its own after this runs.
+    for entity in root_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    commands.remove_resource::<DynamicConfig>();
+    commands.remove_resource::<foliage::FoliageAssets>();
+    commands.remove_resource::<foliage::BushSpawnTimer>();
+    commands.remove_resource::<forest::Forest>();
+    commands.remove_resource::<caterpillar::CaterpillarAssets>();
+    commands.remove_resource::<game::Scoreboard>();
+    commands.remove_resource::<inspector::RenameState>();
+    commands.remove_resource::<minimap::MinimapState>();
+    commands.remove_resource::<capture::CaptureState>();
+    commands.remove_resource::<replay::ReplayPlayback>();
+    commands.remove_resource::<trail::TrailAssets>();
+    commands.remove_resource::<heatmap::Heatmap>();
+    commands.remove_resource::<day_night::DayNight>();
+    commands.remove_resource::<weather::Weather>();
+    commands.insert_resource(FollowCamera::default());
+    commands.insert_resource(SimulationTime::default());
+    commands.insert_resource(CameraModeState::default());
+    commands.insert_resource(pick_events::GroundClick::default());
+    commands.insert_resource(pick_events::HoveredCaterpillar::default());
+    commands.insert_resource(capture::CaptureFrame::default());
+    commands.insert_resource(ClearColor(day_night::DAY_SKY_COLOUR));
+    commands.insert_resource(AmbientLight::default());
 }
 
 fn setup_scene(
@@ -169,34 +359,39 @@ fn setup_scene(
         ..default()
     });
 
-    commands.spawn(PbrBundle {
-        mesh: ground_plane_handle,
-        material: ground_material_handle,
-        transform: Transform::from_xyz(0.0, 0.0, 0.0),
-        ..default()
-    });
+    commands
+        .spawn(PbrBundle {
+            mesh: ground_plane_handle,
+            material: ground_material_handle,
+            transform: Transform::from_xyz(0.0, 0.0, 0.0),
+            ..default()
+        })
+        .insert(PickableBundle::default())
+        .insert(pick_events::Ground);
 
     // directional 'sun' light
     let half_size: f32 = config.plane_size / 2.0;
-    commands.spawn(DirectionalLightBundle {
-        directional_light: DirectionalLight {
-            shadows_enabled: config.enable_shadows,
-            shadow_projection: OrthographicProjection {
-                left: -half_size,
-                right: half_size,
-                bottom: -half_size,
-                top: half_size,
-                near: -100.0 * half_size,
-                far: 100.0 * half_size,
+    commands
+        .spawn(DirectionalLightBundle {
+            directional_light: DirectionalLight {
+                shadows_enabled: config.enable_shadows,
+                shadow_projection: OrthographicProjection {
+                    left: -half_size,
+                    right: half_size,
+                    bottom: -half_size,
+                    top: half_size,
+                    near: -100.0 * half_size,
+                    far: 100.0 * half_size,
+                    ..default()
+                },
+                ..default()
+            },
+            transform: Transform {
+                translation: Vec3::new(0.0, 2.0, 0.0),
+                rotation: Quat::from_rotation_x(-std::f32::consts::FRAC_PI_4),
                 ..default()
             },
             ..default()
-        },
-        transform: Transform {
-            translation: Vec3::new(0.0, 2.0, 0.0),
-            rotation: Quat::from_rotation_x(-std::f32::consts::FRAC_PI_4),
-            ..default()
-        },
-        ..default()
-    });
+        })
+        .insert(day_night::Sun);
 }
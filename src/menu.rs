@@ -0,0 +1,279 @@
+use bevy::{app::AppExit, prelude::*};
+
+use crate::{dynamic_config, replay::LastReplay, AppState};
+
+const BUTTON_COLOUR: Color = Color::rgb(0.15, 0.35, 0.15);
+const BUTTON_HOVER_COLOUR: Color = Color::rgb(0.25, 0.5, 0.25);
+
+/// Keys that pick the menu's entries in order, the configs first and then the last run.
+const NUMBER_KEYS: [KeyCode; 9] = [
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+];
+
+// every config, and the last run after them, needs a number key of its own.
+const _: () = assert!(dynamic_config::CONFIG_FILES.len() < NUMBER_KEYS.len());
+
+#[derive(Component)]
+pub struct MenuUi;
+
+#[derive(Component)]
+pub struct PausedUi;
+
+/// A menu button that loads the given config file.
+#[derive(Component)]
+pub struct ConfigButton {
+    pub data_file: String,
+}
+
+/// A menu button that plays back the last recorded run.
+#[derive(Component)]
+pub struct ReplayButton;
+
+/// Builds a centred block of text, used by the menu and pause overlays.
+pub fn overlay_text(
+    asset_server: &Res<AssetServer>,
+    message: String,
+    font_size: f32,
+) -> TextBundle {
+    let regular_font: Handle<Font> = asset_server.load("fonts/FiraSans-Bold.ttf");
+    TextBundle {
+        style: Style {
+            align_self: AlignSelf::Center,
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                top: Val::Percent(40.0),
+                left: Val::Percent(30.0),
+                ..default()
+            },
+            ..default()
+        },
+        text: Text {
+            sections: vec![TextSection {
+                value: message,
+                style: TextStyle {
+                    font: regular_font,
+                    font_size,
+                    color: Color::WHITE,
+                },
+            }],
+            alignment: Default::default(),
+        },
+        ..default()
+    }
+}
+
+pub fn setup_menu(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    last_replay: Option<Res<LastReplay>>,
+) {
+    let regular_font: Handle<Font> = asset_server.load("fonts/FiraSans-Bold.ttf");
+
+    // the level camera does not exist yet, so the menu brings its own.
+    commands.spawn(Camera2dBundle::default()).insert(MenuUi);
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            ..default()
+        })
+        .insert(MenuUi)
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Caterpillars",
+                TextStyle {
+                    font: regular_font.clone(),
+                    font_size: 60.0,
+                    color: Color::WHITE,
+                },
+            ));
+            parent.spawn(TextBundle::from_section(
+                "Choose a world (or press its number). Esc quits.",
+                TextStyle {
+                    font: regular_font.clone(),
+                    font_size: 20.0,
+                    color: Color::WHITE,
+                },
+            ));
+
+            for (i, data_file) in dynamic_config::CONFIG_FILES.iter().enumerate() {
+                parent
+                    .spawn(ButtonBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(300.0), Val::Px(50.0)),
+                            margin: UiRect::all(Val::Px(8.0)),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        background_color: BUTTON_COLOUR.into(),
+                        ..default()
+                    })
+                    .insert(ConfigButton {
+                        data_file: data_file.to_string(),
+                    })
+                    .with_children(|button| {
+                        button.spawn(TextBundle::from_section(
+                            format!("{}. {}", i + 1, data_file),
+                            TextStyle {
+                                font: regular_font.clone(),
+                                font_size: 26.0,
+                                color: Color::WHITE,
+                            },
+                        ));
+                    });
+            }
+
+            if last_replay.is_some() {
+                parent
+                    .spawn(ButtonBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(300.0), Val::Px(50.0)),
+                            margin: UiRect::all(Val::Px(8.0)),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        background_color: BUTTON_COLOUR.into(),
+                        ..default()
+                    })
+                    .insert(ReplayButton)
+                    .with_children(|button| {
+                        button.spawn(TextBundle::from_section(
+                            format!("{}. Watch last run", dynamic_config::CONFIG_FILES.len() + 1),
+                            TextStyle {
+                                font: regular_font.clone(),
+                                font_size: 26.0,
+                                color: Color::WHITE,
+                            },
+                        ));
+                    });
+            }
+        });
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn menu_system(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    keys: Res<Input<KeyCode>>,
+    last_replay: Option<Res<LastReplay>>,
+    mut app_state: ResMut<State<AppState>>,
+    mut button_query: Query<
+        (&Interaction, &mut BackgroundColor, &ConfigButton),
+        Changed<Interaction>,
+    >,
+    mut replay_button_query: Query<
+        (&Interaction, &mut BackgroundColor),
+        (
+            Changed<Interaction>,
+            With<ReplayButton>,
+            Without<ConfigButton>,
+        ),
+    >,
+    mut ev_exit: EventWriter<AppExit>,
+) {
+    let mut data_file: Option<String> = None;
+    let replay_key = NUMBER_KEYS[dynamic_config::CONFIG_FILES.len()];
+    let mut watch_replay = last_replay.is_some() && keys.just_pressed(replay_key);
+
+    for (interaction, mut colour) in replay_button_query.iter_mut() {
+        match *interaction {
+            Interaction::Clicked => watch_replay = true,
+            Interaction::Hovered => *colour = BUTTON_HOVER_COLOUR.into(),
+            Interaction::None => *colour = BUTTON_COLOUR.into(),
+        }
+    }
+
+    for (interaction, mut colour, button) in button_query.iter_mut() {
+        match *interaction {
+            Interaction::Clicked => data_file = Some(button.data_file.clone()),
+            Interaction::Hovered => *colour = BUTTON_HOVER_COLOUR.into(),
+            Interaction::None => *colour = BUTTON_COLOUR.into(),
+        }
+    }
+
+    for (key, file) in NUMBER_KEYS.iter().zip(dynamic_config::CONFIG_FILES.iter()) {
+        if keys.just_pressed(*key) {
+            data_file = Some(file.to_string());
+        }
+    }
+
+    if keys.just_pressed(KeyCode::Escape) {
+        ev_exit.send(AppExit);
+    }
+
+    if watch_replay {
+        app_state.set(AppState::ReplayLoad).unwrap();
+        return;
+    }
+
+    if let Some(data_file) = data_file {
+        dynamic_config::request_dynamic_config(&mut commands, &asset_server, &data_file);
+        app_state.set(AppState::ConfigLoad).unwrap();
+    }
+}
+
+pub fn teardown_menu(mut commands: Commands, query: Query<Entity, With<MenuUi>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Esc or P pauses and resumes the level. A press is ignored if another state change, such as
+/// the game ending, is already queued this frame.
+pub fn pause_system(mut keys: ResMut<Input<KeyCode>>, mut app_state: ResMut<State<AppState>>) {
+    for key in [KeyCode::Escape, KeyCode::P] {
+        if keys.just_pressed(key) {
+            // the key is reset so the state entered this frame does not see it too.
+            keys.reset(key);
+            let _ = if *app_state.current() == AppState::Paused {
+                app_state.pop()
+            } else {
+                app_state.push(AppState::Paused)
+            };
+            return;
+        }
+    }
+}
+
+pub fn return_to_menu_system(
+    mut keys: ResMut<Input<KeyCode>>,
+    mut app_state: ResMut<State<AppState>>,
+) {
+    if keys.just_pressed(KeyCode::M) {
+        keys.reset(KeyCode::M);
+        // if Esc was pressed in the same frame, whichever key is handled first wins.
+        let _ = app_state.replace(AppState::Menu);
+    }
+}
+
+pub fn setup_paused(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn(overlay_text(
+            &asset_server,
+            "Paused\n\nPress Esc to continue.\nPress M to return to the menu.".to_string(),
+            40.0,
+        ))
+        .insert(PausedUi);
+}
+
+pub fn teardown_paused(mut commands: Commands, query: Query<Entity, With<PausedUi>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,272 @@
+use bevy::{prelude::*, ui::FocusPolicy};
+
+use crate::{
+    camera::{get_primary_window_size, PanOrbitCamera},
+    caterpillar::CaterpillarHead,
+    dynamic_config::DynamicConfig,
+    foliage::{Food, Tree},
+    ui::SelectedCaterpillar,
+};
+
+/// Width and height of the minimap, in pixels.
+const MINIMAP_SIZE: f32 = 200.0;
+
+/// Bush density is shown on a grid of this many cells along each side.
+const DENSITY_CELLS: usize = 16;
+
+/// Bushes in a cell for it to show at full brightness.
+const DENSITY_FULL: f32 = 100.0;
+
+/// Seconds between bush density refreshes.
+const DENSITY_REFRESH_SECONDS: f32 = 1.0;
+
+const CATERPILLAR_COLOUR: Color = Color::rgb(1.0, 0.2, 0.2);
+const SELECTED_COLOUR: Color = Color::rgb(1.0, 1.0, 0.2);
+const TREE_COLOUR: Color = Color::rgb(0.38, 0.23, 0.08);
+
+#[derive(Component)]
+pub struct MinimapUi;
+
+#[derive(Component)]
+pub struct MinimapDensityCell {
+    pub index: usize,
+}
+
+/// A dot on the minimap following a world entity.
+#[derive(Component)]
+pub struct MinimapMarker {
+    pub target: Entity,
+}
+
+#[derive(Resource)]
+pub struct MinimapState {
+    pub density_timer: Timer,
+}
+
+impl Default for MinimapState {
+    fn default() -> Self {
+        MinimapState {
+            density_timer: Timer::from_seconds(DENSITY_REFRESH_SECONDS, TimerMode::Repeating),
+        }
+    }
+}
+
+/// Converts a world position into a pixel position on the minimap, measured from the top left.
+fn world_to_minimap(translation: Vec3, plane_size: f32) -> Vec2 {
+    Vec2::new(
+        (translation.x / plane_size + 0.5) * MINIMAP_SIZE,
+        (translation.z / plane_size + 0.5) * MINIMAP_SIZE,
+    )
+}
+
+fn marker_position(translation: Vec3, plane_size: f32, size: f32) -> UiRect {
+    let position = world_to_minimap(translation, plane_size);
+    UiRect {
+        left: Val::Px(position.x - size / 2.0),
+        top: Val::Px(position.y - size / 2.0),
+        ..default()
+    }
+}
+
+fn marker_bundle(translation: Vec3, plane_size: f32, size: f32, colour: Color) -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            position: marker_position(translation, plane_size, size),
+            size: Size::new(Val::Px(size), Val::Px(size)),
+            ..default()
+        },
+        background_color: colour.into(),
+        // clicks go through to the minimap itself.
+        focus_policy: FocusPolicy::Pass,
+        ..default()
+    }
+}
+
+pub fn setup_minimap(mut commands: Commands) {
+    commands.insert_resource(MinimapState::default());
+
+    let cell_size = MINIMAP_SIZE / DENSITY_CELLS as f32;
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    bottom: Val::Px(45.0),
+                    right: Val::Px(15.0),
+                    ..default()
+                },
+                size: Size::new(Val::Px(MINIMAP_SIZE), Val::Px(MINIMAP_SIZE)),
+                ..default()
+            },
+            background_color: Color::rgba(0.33, 0.49, 0.27, 0.8).into(),
+            ..default()
+        })
+        .insert(Interaction::default())
+        .insert(MinimapUi)
+        .with_children(|parent| {
+            for index in 0..(DENSITY_CELLS * DENSITY_CELLS) {
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            position_type: PositionType::Absolute,
+                            position: UiRect {
+                                left: Val::Px((index % DENSITY_CELLS) as f32 * cell_size),
+                                top: Val::Px((index / DENSITY_CELLS) as f32 * cell_size),
+                                ..default()
+                            },
+                            size: Size::new(Val::Px(cell_size), Val::Px(cell_size)),
+                            ..default()
+                        },
+                        background_color: Color::NONE.into(),
+                        focus_policy: FocusPolicy::Pass,
+                        ..default()
+                    })
+                    .insert(MinimapDensityCell { index });
+            }
+        });
+}
+
+/// N shows and hides the minimap.
+pub fn toggle_minimap_system(
+    keys: Res<Input<KeyCode>>,
+    mut minimap_query: Query<&mut Visibility, With<MinimapUi>>,
+) {
+    if keys.just_pressed(KeyCode::N) {
+        for mut visibility in minimap_query.iter_mut() {
+            visibility.is_visible = !visibility.is_visible;
+        }
+    }
+}
+
+pub fn minimap_density_system(
+    time: Res<Time>,
+    config: Res<DynamicConfig>,
+    mut minimap_state: ResMut<MinimapState>,
+    food_query: Query<&Transform, With<Food>>,
+    mut cell_query: Query<(&MinimapDensityCell, &mut BackgroundColor)>,
+) {
+    if !minimap_state
+        .density_timer
+        .tick(time.delta())
+        .just_finished()
+    {
+        return;
+    }
+
+    let mut counts = [0; DENSITY_CELLS * DENSITY_CELLS];
+    for transform in food_query.iter() {
+        let position = world_to_minimap(transform.translation, config.plane_size) / MINIMAP_SIZE
+            * DENSITY_CELLS as f32;
+        if position.x < 0.0 || position.y < 0.0 {
+            continue;
+        }
+        let (x, y) = (position.x as usize, position.y as usize);
+        if x < DENSITY_CELLS && y < DENSITY_CELLS {
+            counts[y * DENSITY_CELLS + x] += 1;
+        }
+    }
+
+    for (cell, mut colour) in cell_query.iter_mut() {
+        let density = (counts[cell.index] as f32 / DENSITY_FULL).min(1.0);
+        *colour = Color::rgba(0.0, 0.25, 0.0, density).into();
+    }
+}
+
+/// Adds markers for new trees and caterpillars, moves caterpillar markers and removes
+/// markers whose entity has gone.
+#[allow(clippy::too_many_arguments)]
+pub fn minimap_marker_system(
+    mut commands: Commands,
+    config: Res<DynamicConfig>,
+    minimap_query: Query<Entity, With<MinimapUi>>,
+    new_tree_query: Query<(Entity, &Transform), Added<Tree>>,
+    new_caterpillar_query: Query<(Entity, &Transform), Added<CaterpillarHead>>,
+    caterpillar_query: Query<(&Transform, Option<&SelectedCaterpillar>), With<CaterpillarHead>>,
+    tree_query: Query<&Tree>,
+    mut marker_query: Query<(Entity, &MinimapMarker, &mut Style, &mut BackgroundColor)>,
+) {
+    let Ok(minimap) = minimap_query.get_single() else {
+        return;
+    };
+
+    commands.entity(minimap).with_children(|parent| {
+        for (entity, transform) in new_tree_query.iter() {
+            parent
+                .spawn(marker_bundle(
+                    transform.translation,
+                    config.plane_size,
+                    4.0,
+                    TREE_COLOUR,
+                ))
+                .insert(MinimapMarker { target: entity });
+        }
+        for (entity, transform) in new_caterpillar_query.iter() {
+            parent
+                .spawn(marker_bundle(
+                    transform.translation,
+                    config.plane_size,
+                    5.0,
+                    CATERPILLAR_COLOUR,
+                ))
+                .insert(MinimapMarker { target: entity });
+        }
+    });
+
+    for (marker_entity, marker, mut style, mut colour) in marker_query.iter_mut() {
+        if let Ok((transform, selected)) = caterpillar_query.get(marker.target) {
+            let size = if selected.is_some() { 9.0 } else { 5.0 };
+            style.position = marker_position(transform.translation, config.plane_size, size);
+            style.size = Size::new(Val::Px(size), Val::Px(size));
+            *colour = if selected.is_some() {
+                SELECTED_COLOUR
+            } else {
+                CATERPILLAR_COLOUR
+            }
+            .into();
+        } else if !tree_query.contains(marker.target) {
+            commands.entity(marker_entity).despawn_recursive();
+        }
+    }
+}
+
+/// Clicking the minimap moves the camera focus to that spot and stops following.
+pub fn minimap_click_system(
+    mut commands: Commands,
+    windows: Res<Windows>,
+    config: Res<DynamicConfig>,
+    minimap_query: Query<(&Interaction, &GlobalTransform, &Node), Changed<Interaction>>,
+    selected_query: Query<Entity, With<SelectedCaterpillar>>,
+    mut camera_query: Query<&mut PanOrbitCamera>,
+) {
+    for (interaction, global_transform, node) in minimap_query.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        let Some(cursor) = windows
+            .get_primary()
+            .and_then(|window| window.cursor_position())
+        else {
+            continue;
+        };
+        // the window cursor is measured from the bottom left, ui nodes from the top left.
+        let window_size = get_primary_window_size(&windows);
+        let cursor = Vec2::new(cursor.x, window_size.y - cursor.y);
+        let top_left = global_transform.translation().truncate() - node.size() / 2.0;
+        let fraction = (cursor - top_left) / node.size();
+
+        let focus = Vec3::new(
+            (fraction.x - 0.5) * config.plane_size,
+            0.0,
+            (fraction.y - 0.5) * config.plane_size,
+        );
+        for mut pan_orbit in camera_query.iter_mut() {
+            pan_orbit.focus = focus;
+        }
+        for selected_entity in selected_query.iter() {
+            commands
+                .entity(selected_entity)
+                .remove::<SelectedCaterpillar>();
+        }
+    }
+}
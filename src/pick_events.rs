@@ -1,26 +1,166 @@
-use bevy::prelude::*;
+use bevy::{input::mouse::MouseMotion, prelude::*};
 use bevy_mod_picking::*;
 
-use crate::{caterpillar::CaterpillarHead, ui::SelectedCaterpillar};
+use crate::{
+    caterpillar::{CaterpillarAssets, CaterpillarHead, CaterpillarPart, Highlighted},
+    ui::SelectedCaterpillar,
+};
 
+/// Mouse travel, in pixels, after which a click on the ground counts as a drag.
+const DRAG_THRESHOLD: f32 = 5.0;
+
+/// Tags the ground plane so clicking it can clear the selection.
+#[derive(Component)]
+pub struct Ground;
+
+/// A click on the ground waiting to see if it turns into a camera pan.
+#[derive(Resource, Default)]
+pub struct GroundClick {
+    pub pending: bool,
+    pub drag: f32,
+}
+
+/// The caterpillar under the mouse, identified by its head.
+#[derive(Resource, Default)]
+pub struct HoveredCaterpillar(pub Option<Entity>);
+
+/// Finds the head of the caterpillar a picked entity belongs to.
+fn owning_head(
+    entity: Entity,
+    head_query: &Query<&CaterpillarHead>,
+    part_query: &Query<&CaterpillarPart>,
+) -> Option<Entity> {
+    if head_query.contains(entity) {
+        Some(entity)
+    } else {
+        part_query.get(entity).ok().map(|part| part.head)
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn print_events(
     mut commands: Commands,
     mut events: EventReader<PickingEvent>,
-    selected_query: Query<(&mut SelectedCaterpillar, &mut CaterpillarHead, Entity)>,
+    mut ground_click: ResMut<GroundClick>,
+    mut hovered: ResMut<HoveredCaterpillar>,
+    selected_query: Query<Entity, With<SelectedCaterpillar>>,
+    head_query: Query<&CaterpillarHead>,
+    part_query: Query<&CaterpillarPart>,
+    ground_query: Query<&Ground>,
 ) {
     for event in events.iter() {
         match event {
             PickingEvent::Selection(e) => info!("Mesh Selection {:?}", e),
-            PickingEvent::Hover(e) => info!("Mesh Hover {:?}", e),
-            PickingEvent::Clicked(e) => {
-                for (_, _, selected_entity) in selected_query.iter() {
-                    commands
-                        .entity(selected_entity)
-                        .remove::<SelectedCaterpillar>();
+            PickingEvent::Hover(e) => {
+                info!("Mesh Hover {:?}", e);
+                match e {
+                    HoverEvent::JustEntered(entity) => {
+                        hovered.0 = owning_head(*entity, &head_query, &part_query);
+                    }
+                    HoverEvent::JustLeft(entity) => {
+                        if hovered.0.is_some()
+                            && hovered.0 == owning_head(*entity, &head_query, &part_query)
+                        {
+                            hovered.0 = None;
+                        }
+                    }
                 }
-                commands.entity(e.to_owned()).insert(SelectedCaterpillar);
+            }
+            PickingEvent::Clicked(e) => {
                 info!("Mesh clicked {:?}", e);
+                if ground_query.contains(*e) {
+                    ground_click.pending = true;
+                    ground_click.drag = 0.0;
+                    continue;
+                }
+                if let Some(head) = owning_head(*e, &head_query, &part_query) {
+                    for selected_entity in selected_query.iter() {
+                        commands
+                            .entity(selected_entity)
+                            .remove::<SelectedCaterpillar>();
+                    }
+                    commands.entity(head).insert(SelectedCaterpillar);
+                }
+            }
+        }
+    }
+}
+
+/// Clears the selection when the ground is clicked without dragging, or X is pressed.
+pub fn deselect_system(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    input_mouse: Res<Input<MouseButton>>,
+    mut ev_motion: EventReader<MouseMotion>,
+    mut ground_click: ResMut<GroundClick>,
+    selected_query: Query<Entity, With<SelectedCaterpillar>>,
+) {
+    let mut deselect = keys.just_pressed(KeyCode::X);
+
+    if ground_click.pending {
+        for ev in ev_motion.iter() {
+            ground_click.drag += ev.delta.length();
+        }
+        if input_mouse.just_released(MouseButton::Left) {
+            deselect |= ground_click.drag < DRAG_THRESHOLD;
+            ground_click.pending = false;
+        }
+    } else {
+        ev_motion.clear();
+    }
+
+    if deselect {
+        for selected_entity in selected_query.iter() {
+            commands
+                .entity(selected_entity)
+                .remove::<SelectedCaterpillar>();
+        }
+    }
+}
+
+/// Swaps every segment of the hovered caterpillar to the highlight material.
+pub fn hover_highlight_system(
+    mut commands: Commands,
+    hovered: Res<HoveredCaterpillar>,
+    mut highlighted_head: Local<Option<Entity>>,
+    caterpillar_assets: Res<CaterpillarAssets>,
+    head_query: Query<&CaterpillarHead>,
+    part_query: Query<&CaterpillarPart>,
+    mut material_query: Query<(&mut Handle<StandardMaterial>, Option<&Highlighted>)>,
+) {
+    if *highlighted_head == hovered.0 {
+        return;
+    }
+
+    let segments = |head_entity: Entity| {
+        let mut segments = vec![head_entity];
+        let mut caterpillar_part = head_query.get(head_entity).ok().and_then(|h| h.next);
+        while let Some(part_entity) = caterpillar_part {
+            segments.push(part_entity);
+            caterpillar_part = part_query.get(part_entity).ok().and_then(|p| p.next);
+        }
+        segments
+    };
+
+    if let Some(old_head) = *highlighted_head {
+        for entity in segments(old_head) {
+            if let Ok((mut material, Some(highlighted))) = material_query.get_mut(entity) {
+                *material = highlighted.0.clone();
+                commands.entity(entity).remove::<Highlighted>();
             }
         }
     }
+
+    if let Some(new_head) = hovered.0 {
+        for entity in segments(new_head) {
+            if let Ok((mut material, _)) = material_query.get_mut(entity) {
+                commands
+                    .entity(entity)
+                    .insert(Highlighted(material.clone()));
+                *material = caterpillar_assets.highlight_material_handle.clone();
+            }
+        }
+    }
+
+    *highlighted_head = hovered.0;
 }
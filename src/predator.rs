@@ -0,0 +1,383 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+
+use crate::{
+    caterpillar::{self, CaterpillarHead, CaterpillarPart},
+    climbing::Climbing,
+    day_night::Sleeping,
+    dynamic_config::DynamicConfig,
+    foliage::Tree,
+    game::PlayerCaterpillar,
+    random,
+    simulation::SimulationTime,
+    toast::ToastEvent,
+};
+
+/// How close a swooping bird has to get to a caterpillar's head to catch it.
+const CATCH_DISTANCE: f32 = 3.0;
+
+/// Distance from the middle of a bird's body to the middle of each wing.
+const WING_OFFSET: f32 = 4.0;
+
+/// Wing beats per second.
+const FLAP_RATE: f32 = 3.0;
+
+pub enum PredatorState {
+    /// Flying up and over to the tree it will circle next.
+    Rising,
+    Circling,
+    Swooping {
+        target: Entity,
+    },
+}
+
+/// A bird that circles above the trees and swoops down on caterpillars when it is hungry.
+#[derive(Component)]
+pub struct Predator {
+    pub state: PredatorState,
+    /// Middle of the circle the bird flies around, on the ground. Picked when it sets off.
+    centre: Option<Vec3>,
+    angle: f32,
+    /// Simulated seconds until the bird is hungry again.
+    full_for: f32,
+}
+
+impl Predator {
+    pub fn is_hunting(&self) -> bool {
+        match self.state {
+            PredatorState::Swooping { .. } => true,
+            PredatorState::Circling => self.full_for <= 0.0,
+            PredatorState::Rising => false,
+        }
+    }
+}
+
+/// One of a bird's wings. `side` is 1 for the right wing and -1 for the left.
+#[derive(Component)]
+pub struct Wing {
+    side: f32,
+}
+
+/// A caterpillar running from a bird.
+#[derive(Component)]
+pub struct Fleeing;
+
+fn cruise_height(config: &DynamicConfig) -> f32 {
+    config.tree_height + config.predators.altitude
+}
+
+/// A tree for a bird to circle over, or anywhere at all if there are no trees.
+fn pick_centre(
+    tree_query: &Query<&Transform, (With<Tree>, Without<Predator>)>,
+    config: &DynamicConfig,
+) -> Vec3 {
+    let tree_count = tree_query.iter().count() as i32;
+    let centre = if tree_count > 0 {
+        let pick = random::range_i32(0, tree_count) as usize;
+        tree_query
+            .iter()
+            .nth(pick)
+            .map_or(Vec3::ZERO, |transform| transform.translation)
+    } else {
+        random::vec3(config.plane_size / 2.0)
+    };
+    Vec3::new(centre.x, 0.0, centre.z)
+}
+
+fn flat_distance(a: Vec3, b: Vec3) -> f32 {
+    Vec2::new(a.x - b.x, a.z - b.z).length()
+}
+
+/// Turns a bird to face a point, even one straight above or below it.
+fn face(transform: &mut Transform, target: Vec3) {
+    let direction = (target - transform.translation).normalize_or_zero();
+    let up = if direction.dot(Vec3::Y).abs() > 0.99 {
+        Vec3::Z
+    } else {
+        Vec3::Y
+    };
+    transform.look_at(target, up);
+}
+
+pub fn setup_predators(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    config: Res<DynamicConfig>,
+) {
+    if config.predators.count == 0 {
+        return;
+    }
+
+    let body_mesh_handle = meshes.add(Mesh::from(shape::UVSphere {
+        radius: 2.0,
+        sectors: 12,
+        stacks: 8,
+    }));
+    let wing_mesh_handle = meshes.add(Mesh::from(shape::Box::new(
+        WING_OFFSET * 2.0 - 2.0,
+        0.3,
+        3.0,
+    )));
+    let feather_material_handle = materials.add(StandardMaterial {
+        base_color: Color::rgb(0.15, 0.12, 0.1),
+        perceptual_roughness: 1.0,
+        ..default()
+    });
+
+    let half_size = config.plane_size / 2.0;
+    for _ in 0..config.predators.count {
+        let mut translation = random::vec3(half_size);
+        translation.y = cruise_height(&config);
+        let mut transform = Transform::from_translation(translation);
+        // the body is stretched out from beak to tail.
+        transform.scale = Vec3::new(1.0, 0.6, 2.0);
+
+        commands
+            .spawn(PbrBundle {
+                mesh: body_mesh_handle.clone(),
+                material: feather_material_handle.clone(),
+                transform,
+                ..default()
+            })
+            .insert(Predator {
+                state: PredatorState::Rising,
+                centre: None,
+                angle: random::range_f32(0.0, TAU),
+                // birds start out peckish at different times.
+                full_for: random::range_f32(0.0, config.predators.hunger),
+            })
+            .with_children(|parent| {
+                for side in [-1.0, 1.0] {
+                    parent
+                        .spawn(PbrBundle {
+                            mesh: wing_mesh_handle.clone(),
+                            material: feather_material_handle.clone(),
+                            // undo the body's stretch so the wings keep their shape.
+                            transform: Transform::from_xyz(side * WING_OFFSET, 0.0, 0.0)
+                                .with_scale(Vec3::new(1.0, 1.0 / 0.6, 0.5)),
+                            ..default()
+                        })
+                        .insert(Wing { side });
+                }
+            });
+    }
+}
+
+/// Flies birds around their trees. Hungry birds swoop on the nearest caterpillar they can see
+/// and make off with its tail, or the whole caterpillar.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn predator_system(
+    mut commands: Commands,
+    simulation_time: Res<SimulationTime>,
+    config: Res<DynamicConfig>,
+    mut predator_query: Query<(&mut Transform, &mut Predator)>,
+    mut head_query: Query<
+        (
+            Entity,
+            &Transform,
+            &mut CaterpillarHead,
+            Option<&PlayerCaterpillar>,
+        ),
+        Without<Predator>,
+    >,
+    mut part_query: Query<&mut CaterpillarPart>,
+    tree_query: Query<&Transform, (With<Tree>, Without<Predator>)>,
+    mut ev_toast: EventWriter<ToastEvent>,
+) {
+    let delta = simulation_time.delta_seconds();
+    if delta == 0.0 {
+        return;
+    }
+    let predator_config = &config.predators;
+    let height = cruise_height(&config);
+    let radius = predator_config.circle_radius.max(1.0);
+    // caterpillars already caught this frame, so two birds don't take the same one.
+    let mut caught: Vec<Entity> = Vec::new();
+
+    for (mut transform, mut predator) in predator_query.iter_mut() {
+        predator.full_for = (predator.full_for - delta).max(0.0);
+
+        match predator.state {
+            PredatorState::Rising => {
+                let centre = *predator
+                    .centre
+                    .get_or_insert_with(|| pick_centre(&tree_query, &config));
+                let target = centre
+                    + Vec3::new(
+                        predator.angle.cos() * radius,
+                        height,
+                        predator.angle.sin() * radius,
+                    );
+                let step = predator_config.speed * delta;
+                if transform.translation.distance(target) <= step {
+                    transform.translation = target;
+                    predator.state = PredatorState::Circling;
+                } else {
+                    face(&mut transform, target);
+                    let forward = transform.forward();
+                    transform.translation += forward * step;
+                }
+            }
+            PredatorState::Circling => {
+                let Some(centre) = predator.centre else {
+                    predator.state = PredatorState::Rising;
+                    continue;
+                };
+                predator.angle += predator_config.speed / radius * delta;
+                let offset = Vec3::new(predator.angle.cos(), 0.0, predator.angle.sin());
+                transform.translation = centre + offset * radius + Vec3::Y * height;
+                let tangent = Vec3::new(-offset.z, 0.0, offset.x);
+                let ahead = transform.translation + tangent;
+                transform.look_at(ahead, Vec3::Y);
+
+                if predator.full_for > 0.0 {
+                    predator.angle = predator.angle.rem_euclid(TAU);
+                    continue;
+                }
+                let prey = head_query
+                    .iter()
+                    .filter(|(entity, ..)| !caught.contains(entity))
+                    .map(|(entity, head_transform, ..)| {
+                        (
+                            entity,
+                            flat_distance(transform.translation, head_transform.translation),
+                        )
+                    })
+                    .filter(|(_, distance)| *distance <= predator_config.sight_radius)
+                    .min_by(|a, b| a.1.total_cmp(&b.1))
+                    .map(|(entity, _)| entity);
+                if let Some(target) = prey {
+                    predator.state = PredatorState::Swooping { target };
+                } else if predator.angle > TAU {
+                    // nothing to eat round here, try another tree.
+                    predator.angle -= TAU;
+                    predator.centre = None;
+                    predator.state = PredatorState::Rising;
+                }
+            }
+            PredatorState::Swooping { target } => {
+                let Ok((_, head_transform, mut caterpillar, player)) = head_query.get_mut(target)
+                else {
+                    predator.state = PredatorState::Rising;
+                    continue;
+                };
+                if caught.contains(&target) {
+                    predator.state = PredatorState::Rising;
+                    continue;
+                }
+
+                let step = predator_config.swoop_speed * delta;
+                let distance = transform.translation.distance(head_transform.translation);
+                if distance > CATCH_DISTANCE.max(step) {
+                    face(&mut transform, head_transform.translation);
+                    let forward = transform.forward();
+                    transform.translation += forward * step;
+                    continue;
+                }
+
+                // the player's caterpillar only ever loses its tail, or the game would be over.
+                let tail_only = player.is_some()
+                    || random::range_f32(0.0, 1.0) < predator_config.tail_only_chance;
+                let message = if tail_only
+                    && caterpillar::remove_tail_segment(
+                        &mut commands,
+                        &mut caterpillar,
+                        &mut part_query,
+                    ) {
+                    format!("A bird bit off {}'s tail!", caterpillar.name)
+                } else if player.is_none() {
+                    caterpillar::despawn_caterpillar(
+                        &mut commands,
+                        target,
+                        &caterpillar,
+                        &part_query.to_readonly(),
+                    );
+                    caught.push(target);
+                    format!("A bird snatched {}!", caterpillar.name)
+                } else {
+                    format!("{} got away from a bird!", caterpillar.name)
+                };
+                info!("{}", message);
+                ev_toast.send(ToastEvent {
+                    message,
+                    expiry_tick: 5000,
+                });
+
+                predator.full_for = predator_config.hunger;
+                predator.centre = None;
+                predator.state = PredatorState::Rising;
+            }
+        }
+    }
+}
+
+/// Sends caterpillars running from any hunting bird that comes too close, and lets them calm
+/// down once it has gone.
+#[allow(clippy::type_complexity)]
+pub fn flee_system(
+    mut commands: Commands,
+    config: Res<DynamicConfig>,
+    predator_query: Query<(&Transform, &Predator)>,
+    mut head_query: Query<
+        (Entity, &Transform, &mut CaterpillarHead, Option<&Fleeing>),
+        (Without<Predator>, Without<Climbing>, Without<Sleeping>),
+    >,
+) {
+    let predator_config = &config.predators;
+    for (entity, transform, mut caterpillar, fleeing) in head_query.iter_mut() {
+        if caterpillar.manually_controlled {
+            continue;
+        }
+
+        let threat = predator_query
+            .iter()
+            .filter(|(_, predator)| predator.is_hunting())
+            .map(|(predator_transform, _)| predator_transform.translation)
+            .filter(|position| {
+                flat_distance(*position, transform.translation) <= predator_config.flee_radius
+            })
+            .min_by(|a, b| {
+                flat_distance(*a, transform.translation)
+                    .total_cmp(&flat_distance(*b, transform.translation))
+            });
+
+        match threat {
+            Some(position) => {
+                caterpillar.angle = caterpillar::heading_to(position, transform.translation);
+                if fleeing.is_none() {
+                    caterpillar.angle_offset = 0.0;
+                    commands.entity(entity).insert(Fleeing);
+                }
+            }
+            None => {
+                if fleeing.is_some() {
+                    commands.entity(entity).remove::<Fleeing>();
+                }
+            }
+        }
+    }
+}
+
+/// Flaps birds' wings, holding them up while they swoop.
+pub fn wing_system(
+    simulation_time: Res<SimulationTime>,
+    predator_query: Query<&Predator>,
+    mut wing_query: Query<(&mut Transform, &Wing, &Parent)>,
+) {
+    let time = simulation_time.elapsed_seconds();
+    for (mut transform, wing, parent) in wing_query.iter_mut() {
+        let Ok(predator) = predator_query.get(parent.get()) else {
+            continue;
+        };
+        let lift = match predator.state {
+            PredatorState::Swooping { .. } => 0.6,
+            _ => (time * FLAP_RATE * TAU).sin() * 0.5,
+        };
+        // wings pivot where they meet the body.
+        let rotation = Quat::from_rotation_z(lift * wing.side);
+        transform.rotation = rotation;
+        transform.translation = rotation * Vec3::new(wing.side * WING_OFFSET, 0.0, 0.0);
+    }
+}
@@ -0,0 +1,1035 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::prelude::*;
+#[cfg(not(target_arch = "wasm32"))]
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+
+use crate::{
+    caterpillar::{CaterpillarAssets, CaterpillarHead, CaterpillarPart},
+    climbing::CanopyFood,
+    dynamic_config::DynamicConfig,
+    foliage::{self, FoliageAssets, Food, FoodEatenEvent, Tree},
+    simulation::{SimulationTime, NORMAL_SPEED_INDEX, SPEEDS},
+    AppState,
+};
+
+/// Seconds jumped by the arrow keys.
+const SEEK_SECONDS: f32 = 5.0;
+
+/// Shortest gap between recorded frames, in simulated seconds.
+const MIN_SAMPLE_INTERVAL: f32 = 0.02;
+
+/// Width of the scrub bar, in pixels.
+const SCRUB_BAR_WIDTH: f32 = 600.0;
+
+/// Segment positions are saved as whole numbers of this fraction of a unit.
+const POSITION_STEPS: f32 = 10.0;
+
+/// Segment headings are saved as whole numbers of this fraction of a radian.
+const YAW_STEPS: f32 = 100.0;
+
+/// A recorded run, everything needed to play it back without the simulation.
+#[derive(serde::Deserialize, serde::Serialize, Resource, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Replay {
+    pub config: DynamicConfig,
+    pub trees: Vec<[f32; 3]>,
+    pub frames: Vec<ReplayFrame>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplayFrame {
+    /** Simulated seconds since the level started. */
+    pub time: f32,
+    pub caterpillars: Vec<CaterpillarSnapshot>,
+    /** Everything that happened since the previous frame. */
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<ReplayEvent>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CaterpillarSnapshot {
+    pub id: u32,
+    /** x, y and z in tenths of a unit and yaw in hundredths of a radian of every segment, head
+    first. */
+    pub segments: Vec<[i32; 4]>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone, PartialEq, Debug)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum ReplayEvent {
+    Spawn {
+        id: u32,
+        name: String,
+        #[serde(default)]
+        palette: usize,
+    },
+    Despawn {
+        id: u32,
+    },
+    FoodSpawn {
+        id: u32,
+        position: [f32; 3],
+        #[serde(default)]
+        kind: usize,
+    },
+    FoodEaten {
+        id: u32,
+    },
+    /// A bush shrank from a bite or grew back.
+    FoodResized {
+        id: u32,
+        scale: f32,
+    },
+    /// One of the spheres of a tree's canopy shrank or grew back. `tree` indexes the replay's
+    /// trees and `canopy` the tree's children.
+    CanopyResized {
+        tree: u32,
+        canopy: u32,
+        scale: f32,
+    },
+}
+
+/// The replay from the last run, kept after the level is torn down so it can be watched.
+#[derive(Resource)]
+pub struct LastReplay(pub Replay);
+
+/// Builds up a replay while the level plays. Only present when `replay.record` is set.
+#[derive(Resource)]
+pub struct ReplayRecorder {
+    replay: Replay,
+    sample_timer: Timer,
+    next_id: u32,
+    caterpillar_ids: HashMap<Entity, u32>,
+    food_ids: HashMap<Entity, u32>,
+    /// Index of each tree in the replay's trees.
+    tree_ids: HashMap<Entity, u32>,
+    /// Bushes and canopy spheres that have changed size since the last frame was taken.
+    resized: HashSet<Entity>,
+    /// Scale last recorded for each bush and canopy sphere.
+    scales: HashMap<Entity, f32>,
+    events: Vec<ReplayEvent>,
+}
+
+impl ReplayRecorder {
+    fn next_id(&mut self) -> u32 {
+        self.next_id += 1;
+        self.next_id
+    }
+}
+
+#[derive(Component)]
+pub struct ReplayUi;
+
+#[derive(Component)]
+pub struct ReplayTextUi;
+
+#[derive(Component)]
+pub struct ScrubBar;
+
+#[derive(Component)]
+pub struct ScrubBarFill;
+
+/// A bush as it is at some point in a replay.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ReplayFood {
+    pub position: [f32; 3],
+    pub kind: usize,
+    pub scale: f32,
+}
+
+/// A caterpillar in a replay.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ReplayCaterpillar {
+    pub name: String,
+    /// Index of the palette it is painted in.
+    pub palette: usize,
+}
+
+/// Everything the events of a replay say about the world at some point in it.
+#[derive(Default)]
+pub struct ReplayWorld {
+    /// Number of frames whose events have been applied.
+    pub applied_frames: usize,
+    /// The caterpillars alive, by id.
+    pub caterpillars: HashMap<u32, ReplayCaterpillar>,
+    pub food: HashMap<u32, ReplayFood>,
+    /// Scale of each canopy sphere that has changed size, by tree and sphere.
+    pub canopies: HashMap<(u32, u32), f32>,
+}
+
+impl ReplayWorld {
+    fn apply(&mut self, event: &ReplayEvent) {
+        match event {
+            ReplayEvent::Spawn { id, name, palette } => {
+                self.caterpillars.insert(
+                    *id,
+                    ReplayCaterpillar {
+                        name: name.clone(),
+                        palette: *palette,
+                    },
+                );
+            }
+            ReplayEvent::Despawn { id } => {
+                self.caterpillars.remove(id);
+            }
+            ReplayEvent::FoodSpawn { id, position, kind } => {
+                self.food.insert(
+                    *id,
+                    ReplayFood {
+                        position: *position,
+                        kind: *kind,
+                        scale: 1.0,
+                    },
+                );
+            }
+            ReplayEvent::FoodEaten { id } => {
+                self.food.remove(id);
+            }
+            ReplayEvent::FoodResized { id, scale } => {
+                if let Some(food) = self.food.get_mut(id) {
+                    food.scale = *scale;
+                }
+            }
+            ReplayEvent::CanopyResized {
+                tree,
+                canopy,
+                scale,
+            } => {
+                self.canopies.insert((*tree, *canopy), *scale);
+            }
+        }
+    }
+
+    /// Applies the events of every frame up to `time`, starting again from the beginning when
+    /// seeking backwards. Returns whether anything changed.
+    pub fn seek(&mut self, replay: &Replay, time: f32) -> bool {
+        let mut changed = false;
+        let applied_time = self
+            .applied_frames
+            .checked_sub(1)
+            .map(|i| replay.frames[i].time);
+        if applied_time.map_or(false, |applied_time| applied_time > time) {
+            *self = ReplayWorld::default();
+            changed = true;
+        }
+
+        while self.applied_frames < replay.frames.len()
+            && replay.frames[self.applied_frames].time <= time
+        {
+            for event in replay.frames[self.applied_frames].events.iter() {
+                self.apply(event);
+            }
+            self.applied_frames += 1;
+            changed = true;
+        }
+        changed
+    }
+}
+
+#[derive(Resource)]
+pub struct ReplayPlayback {
+    time: f32,
+    speed_index: usize,
+    world: ReplayWorld,
+    caterpillars: HashMap<u32, Vec<Entity>>,
+    food: HashMap<u32, Entity>,
+    trees: Vec<Entity>,
+    /// Full size of each canopy sphere that has been resized, to go back to when seeking.
+    canopy_rest: HashMap<Entity, Vec3>,
+}
+
+/// Rounds to two decimal places, which keeps the saved file small.
+fn round(value: f32) -> f32 {
+    (value * 100.0).round() / 100.0
+}
+
+fn snapshot_segment(transform: &Transform) -> [i32; 4] {
+    let (yaw, _, _) = transform.rotation.to_euler(EulerRot::YXZ);
+    let translation = transform.translation * POSITION_STEPS;
+    [
+        translation.x.round() as i32,
+        translation.y.round() as i32,
+        translation.z.round() as i32,
+        (yaw * YAW_STEPS).round() as i32,
+    ]
+}
+
+fn position(translation: Vec3) -> [f32; 3] {
+    [
+        round(translation.x),
+        round(translation.y),
+        round(translation.z),
+    ]
+}
+
+pub fn setup_replay_recorder(mut commands: Commands, config: Res<DynamicConfig>) {
+    if !config.replay.record {
+        return;
+    }
+    commands.insert_resource(ReplayRecorder {
+        replay: Replay {
+            config: config.clone(),
+            trees: Vec::new(),
+            frames: Vec::new(),
+        },
+        sample_timer: Timer::from_seconds(
+            config.replay.sample_interval.max(MIN_SAMPLE_INTERVAL),
+            TimerMode::Repeating,
+        ),
+        next_id: 0,
+        caterpillar_ids: HashMap::new(),
+        food_ids: HashMap::new(),
+        tree_ids: HashMap::new(),
+        resized: HashSet::new(),
+        scales: HashMap::new(),
+        events: Vec::new(),
+    });
+}
+
+/// Notes trees and bushes as they appear, bushes being finished off, and bushes and canopies
+/// changing size, which is recorded with the next frame.
+#[allow(clippy::type_complexity)]
+pub fn record_foliage_system(
+    recorder: Option<ResMut<ReplayRecorder>>,
+    new_tree_query: Query<(Entity, &Transform), Added<Tree>>,
+    new_food_query: Query<(Entity, &Transform, &Food), Added<Food>>,
+    food_query: Query<&Food>,
+    resized_query: Query<Entity, Or<(Changed<Food>, Changed<CanopyFood>)>>,
+    mut ev_eaten: EventReader<FoodEatenEvent>,
+) {
+    let Some(mut recorder) = recorder else {
+        ev_eaten.clear();
+        return;
+    };
+
+    for (entity, transform) in new_tree_query.iter() {
+        let index = recorder.replay.trees.len() as u32;
+        recorder.tree_ids.insert(entity, index);
+        recorder.replay.trees.push(position(transform.translation));
+    }
+
+    for (entity, transform, food) in new_food_query.iter() {
+        let id = recorder.next_id();
+        recorder.food_ids.insert(entity, id);
+        recorder.events.push(ReplayEvent::FoodSpawn {
+            id,
+            position: position(transform.translation),
+            kind: food.kind,
+        });
+    }
+
+    // the bite that finishes off a bush despawns it through commands, and the removal is gone
+    // again by the time a system in the same stage could see it.
+    for event in ev_eaten.iter() {
+        let Some(entity) = event.food else {
+            continue;
+        };
+        if food_query
+            .get(entity)
+            .map_or(false, |food| food.amount > 0.0)
+        {
+            continue;
+        }
+        if let Some(id) = recorder.food_ids.remove(&entity) {
+            recorder.resized.remove(&entity);
+            recorder.scales.remove(&entity);
+            recorder.events.push(ReplayEvent::FoodEaten { id });
+        }
+    }
+
+    for entity in resized_query.iter() {
+        recorder.resized.insert(entity);
+    }
+}
+
+/// Records the size of bushes and canopy spheres that have changed since the last frame.
+#[allow(clippy::type_complexity)]
+fn record_resized(
+    recorder: &mut ReplayRecorder,
+    scale_query: &Query<(&Transform, Option<&Parent>), Or<(With<Food>, With<CanopyFood>)>>,
+    children_query: &Query<&Children, With<Tree>>,
+) {
+    for entity in std::mem::take(&mut recorder.resized) {
+        let Ok((transform, parent)) = scale_query.get(entity) else {
+            continue;
+        };
+        let scale = round(transform.scale.x);
+        let previous = recorder.scales.insert(entity, scale);
+
+        if let Some(id) = recorder.food_ids.get(&entity).copied() {
+            // bushes are spawned at full size.
+            if previous.unwrap_or(1.0) != scale {
+                recorder.events.push(ReplayEvent::FoodResized { id, scale });
+            }
+            continue;
+        }
+
+        // canopies are first seen fully grown, as they are when the replay's trees are planted.
+        if previous.map_or(true, |previous| previous == scale) {
+            continue;
+        }
+        let Some(tree_entity) = parent.map(|parent| parent.get()) else {
+            continue;
+        };
+        let tree = recorder.tree_ids.get(&tree_entity).copied();
+        let canopy = children_query
+            .get(tree_entity)
+            .ok()
+            .and_then(|children| children.iter().position(|child| *child == entity));
+        if let (Some(tree), Some(canopy)) = (tree, canopy) {
+            recorder.events.push(ReplayEvent::CanopyResized {
+                tree,
+                canopy: canopy as u32,
+                scale,
+            });
+        }
+    }
+}
+
+/// Notes caterpillars coming and going, and samples every caterpillar's segments, along with
+/// the size of any bushes and canopies that changed, each `replay.sampleInterval` simulated
+/// seconds.
+#[allow(clippy::type_complexity)]
+pub fn record_replay_system(
+    simulation_time: Res<SimulationTime>,
+    recorder: Option<ResMut<ReplayRecorder>>,
+    caterpillar_query: Query<(Entity, &CaterpillarHead, &Transform)>,
+    part_query: Query<(&CaterpillarPart, &Transform)>,
+    scale_query: Query<(&Transform, Option<&Parent>), Or<(With<Food>, With<CanopyFood>)>>,
+    children_query: Query<&Children, With<Tree>>,
+) {
+    let Some(mut recorder) = recorder else {
+        return;
+    };
+
+    for (entity, caterpillar, _) in caterpillar_query.iter() {
+        if !recorder.caterpillar_ids.contains_key(&entity) {
+            let id = recorder.next_id();
+            recorder.caterpillar_ids.insert(entity, id);
+            recorder.events.push(ReplayEvent::Spawn {
+                id,
+                name: caterpillar.name.clone(),
+                palette: caterpillar.palette,
+            });
+        }
+    }
+    let despawned: Vec<Entity> = recorder
+        .caterpillar_ids
+        .keys()
+        .filter(|entity| !caterpillar_query.contains(**entity))
+        .copied()
+        .collect();
+    for entity in despawned {
+        if let Some(id) = recorder.caterpillar_ids.remove(&entity) {
+            recorder.events.push(ReplayEvent::Despawn { id });
+        }
+    }
+
+    // the first frame is always taken so the replay starts with the whole world.
+    let first_frame = recorder.replay.frames.is_empty();
+    if !recorder
+        .sample_timer
+        .tick(simulation_time.delta())
+        .just_finished()
+        && !first_frame
+    {
+        return;
+    }
+
+    record_resized(&mut recorder, &scale_query, &children_query);
+
+    let mut caterpillars = Vec::new();
+    for (entity, caterpillar, transform) in caterpillar_query.iter() {
+        let mut segments = vec![snapshot_segment(transform)];
+        let mut caterpillar_part = caterpillar.next;
+        while let Some(part_entity) = caterpillar_part {
+            let Ok((part, part_transform)) = part_query.get(part_entity) else {
+                break;
+            };
+            segments.push(snapshot_segment(part_transform));
+            caterpillar_part = part.next;
+        }
+        caterpillars.push(CaterpillarSnapshot {
+            id: recorder.caterpillar_ids[&entity],
+            segments,
+        });
+    }
+    let events = std::mem::take(&mut recorder.events);
+    recorder.replay.frames.push(ReplayFrame {
+        time: round(simulation_time.elapsed_seconds()),
+        caterpillars,
+        events,
+    });
+}
+
+/// Keeps the finished recording for the menu and, outside the browser, saves it to
+/// `replay.directory`.
+pub fn finish_replay_recording(mut commands: Commands, recorder: Option<Res<ReplayRecorder>>) {
+    let Some(recorder) = recorder else {
+        return;
+    };
+    commands.remove_resource::<ReplayRecorder>();
+    if recorder.replay.frames.is_empty() {
+        return;
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let directory = &recorder.replay.config.replay.directory;
+        let path = format!(
+            "{}/replay-{}.json.gz",
+            directory,
+            crate::capture::timestamp()
+        );
+        let saved = std::fs::create_dir_all(directory)
+            .map_err(|e| e.to_string())
+            .and_then(|_| encode_replay(&recorder.replay))
+            .and_then(|bytes| std::fs::write(&path, bytes).map_err(|e| e.to_string()));
+        match saved {
+            Ok(()) => info!("Saved replay to {}.", path),
+            Err(e) => error!("Could not save replay to {}: {}", path, e),
+        }
+    }
+
+    commands.insert_resource(LastReplay(recorder.replay.clone()));
+}
+
+/// Gzipped JSON, which shrinks the long runs of similar frames down to a small file.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn encode_replay(replay: &Replay) -> Result<Vec<u8>, String> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    serde_json::to_writer(&mut encoder, replay).map_err(|e| e.to_string())?;
+    encoder.finish().map_err(|e| e.to_string())
+}
+
+/// Reads a replay written by `encode_replay`, or plain JSON.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn decode_replay(bytes: &[u8]) -> Result<Replay, String> {
+    // every gzip stream starts with these two bytes, and JSON never does.
+    if bytes.starts_with(&[0x1f, 0x8b]) {
+        serde_json::from_reader(GzDecoder::new(bytes)).map_err(|e| e.to_string())
+    } else {
+        serde_json::from_slice(bytes).map_err(|e| e.to_string())
+    }
+}
+
+/// Reads a replay saved by a previous run.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load_replay_file(path: &str) -> Result<Replay, String> {
+    let bytes = std::fs::read(path).map_err(|e| e.to_string())?;
+    decode_replay(&bytes)
+}
+
+/// Sets up the config the replay was recorded with, so the world's assets can be built from it.
+pub fn start_replay_loading(mut commands: Commands, last_replay: Res<LastReplay>) {
+    commands.insert_resource(last_replay.0.config.clone());
+}
+
+pub fn replay_loading_completed(mut app_state: ResMut<State<AppState>>) {
+    app_state.set(AppState::Replay).unwrap();
+}
+
+pub fn setup_replay(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    last_replay: Res<LastReplay>,
+    foliage_assets: Res<FoliageAssets>,
+) {
+    let replay = &last_replay.0;
+    let trees = replay
+        .trees
+        .iter()
+        .map(|tree| {
+            foliage::spawn_tree(
+                &mut commands,
+                &foliage_assets,
+                Vec3::from(*tree),
+                replay.config.tree_height,
+            )
+        })
+        .collect();
+
+    commands.insert_resource(ReplayPlayback {
+        time: 0.0,
+        speed_index: NORMAL_SPEED_INDEX,
+        world: ReplayWorld::default(),
+        caterpillars: HashMap::new(),
+        food: HashMap::new(),
+        trees,
+        canopy_rest: HashMap::new(),
+    });
+
+    let mono_font = asset_server.load("fonts/FiraMono-Regular.ttf");
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    bottom: Val::Px(15.0),
+                    left: Val::Px(15.0),
+                    ..default()
+                },
+                flex_direction: FlexDirection::Column,
+                ..default()
+            },
+            ..default()
+        })
+        .insert(ReplayUi)
+        .with_children(|parent| {
+            parent
+                .spawn(TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: mono_font,
+                        font_size: 20.0,
+                        color: Color::WHITE,
+                    },
+                ))
+                .insert(ReplayTextUi);
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(SCRUB_BAR_WIDTH), Val::Px(16.0)),
+                        margin: UiRect {
+                            top: Val::Px(6.0),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    background_color: Color::rgba(0.0, 0.0, 0.0, 0.5).into(),
+                    ..default()
+                })
+                .insert(Interaction::default())
+                .insert(ScrubBar)
+                .with_children(|bar| {
+                    bar.spawn(NodeBundle {
+                        style: Style {
+                            size: Size::new(Val::Percent(0.0), Val::Percent(100.0)),
+                            ..default()
+                        },
+                        background_color: Color::rgb(0.9, 0.9, 0.2).into(),
+                        focus_policy: bevy::ui::FocusPolicy::Pass,
+                        ..default()
+                    })
+                    .insert(ScrubBarFill);
+                });
+        });
+}
+
+fn replay_duration(replay: &Replay) -> f32 {
+    replay.frames.last().map_or(0.0, |frame| frame.time)
+}
+
+/// Space plays and pauses, the arrow keys and the scrub bar seek, `[` and `]` change speed and
+/// Esc goes back to the menu.
+#[allow(clippy::too_many_arguments)]
+pub fn replay_controls_system(
+    mut keys: ResMut<Input<KeyCode>>,
+    input_mouse: Res<Input<MouseButton>>,
+    windows: Res<Windows>,
+    last_replay: Res<LastReplay>,
+    mut playback: ResMut<ReplayPlayback>,
+    mut app_state: ResMut<State<AppState>>,
+    mut resume_index: Local<Option<usize>>,
+    scrub_query: Query<(&Interaction, &GlobalTransform, &Node), With<ScrubBar>>,
+) {
+    let duration = replay_duration(&last_replay.0);
+
+    if keys.just_pressed(KeyCode::Escape) {
+        // the menu would otherwise see the same press and quit.
+        keys.reset(KeyCode::Escape);
+        app_state.set(AppState::Menu).unwrap();
+        return;
+    }
+    if keys.just_pressed(KeyCode::RBracket) && playback.speed_index < SPEEDS.len() - 1 {
+        playback.speed_index += 1;
+    }
+    if keys.just_pressed(KeyCode::LBracket) && playback.speed_index > 0 {
+        playback.speed_index -= 1;
+    }
+    if keys.just_pressed(KeyCode::Space) {
+        if playback.speed_index == 0 {
+            playback.speed_index = resume_index.take().unwrap_or(NORMAL_SPEED_INDEX);
+            // playing from the end starts again.
+            if playback.time >= duration {
+                playback.time = 0.0;
+            }
+        } else {
+            *resume_index = Some(playback.speed_index);
+            playback.speed_index = 0;
+        }
+    }
+    if keys.just_pressed(KeyCode::Left) {
+        playback.time = (playback.time - SEEK_SECONDS).max(0.0);
+    }
+    if keys.just_pressed(KeyCode::Right) {
+        playback.time = (playback.time + SEEK_SECONDS).min(duration);
+    }
+    if keys.just_pressed(KeyCode::Home) {
+        playback.time = 0.0;
+    }
+
+    // dragging along the scrub bar seeks to that point.
+    for (interaction, global_transform, node) in scrub_query.iter() {
+        if *interaction != Interaction::Clicked || !input_mouse.pressed(MouseButton::Left) {
+            continue;
+        }
+        let Some(window) = windows.get_primary() else {
+            continue;
+        };
+        let Some(cursor) = window.cursor_position() else {
+            continue;
+        };
+        let left = global_transform.translation().x - node.size().x / 2.0;
+        let fraction = ((cursor.x - left) / node.size().x).clamp(0.0, 1.0);
+        playback.time = fraction * duration;
+    }
+}
+
+fn spawn_replay_segment(
+    commands: &mut Commands,
+    caterpillar_assets: &CaterpillarAssets,
+    palette: usize,
+    segment: usize,
+) -> Entity {
+    let mesh = if segment == 0 {
+        caterpillar_assets.head_meshes.mesh()
+    } else {
+        caterpillar_assets.body_meshes.mesh()
+    };
+    commands
+        .spawn(PbrBundle {
+            mesh,
+            material: caterpillar_assets.segment_material(palette, segment),
+            ..default()
+        })
+        .id()
+}
+
+fn segment_transform(from: &[i32; 4], to: &[i32; 4], t: f32) -> Transform {
+    let translation = |segment: &[i32; 4]| {
+        Vec3::new(segment[0] as f32, segment[1] as f32, segment[2] as f32) / POSITION_STEPS
+    };
+    let rotation = |segment: &[i32; 4]| Quat::from_rotation_y(segment[3] as f32 / YAW_STEPS);
+    Transform::from_translation(translation(from).lerp(translation(to), t))
+        .with_rotation(rotation(from).slerp(rotation(to), t))
+}
+
+/// Sets a transform that may have been spawned this frame, in which case it can't be reached
+/// through the query yet.
+fn set_transform(
+    commands: &mut Commands,
+    transform_query: &mut Query<&mut Transform>,
+    entity: Entity,
+    transform: Transform,
+) {
+    if let Ok(mut current) = transform_query.get_mut(entity) {
+        if *current != transform {
+            *current = transform;
+        }
+    } else {
+        commands.entity(entity).insert(transform);
+    }
+}
+
+/// Spawns and despawns bushes and sizes them and the canopies to match the replay's world.
+fn sync_foliage(
+    commands: &mut Commands,
+    replay: &Replay,
+    foliage_assets: &FoliageAssets,
+    playback: &mut ReplayPlayback,
+    transform_query: &mut Query<&mut Transform>,
+    children_query: &Query<&Children>,
+) {
+    let world = &playback.world;
+    playback.food.retain(|id, entity| {
+        let alive = world.food.contains_key(id);
+        if !alive {
+            commands.entity(*entity).despawn_recursive();
+        }
+        alive
+    });
+    for (id, food) in world.food.iter() {
+        let entity = *playback.food.entry(*id).or_insert_with(|| {
+            foliage::spawn_bush(
+                commands,
+                foliage_assets,
+                Vec3::from(food.position),
+                food.kind,
+            )
+        });
+        let mut transform = Transform::from_translation(Vec3::from(food.position));
+        if let Some(food_type) = replay.config.foods.get(food.kind) {
+            foliage::scale_transform(&mut transform, food_type, food.scale);
+        }
+        set_transform(commands, transform_query, entity, transform);
+    }
+
+    // canopies that have been resized before go back to full size unless the world says not.
+    let mut canopy_scales = playback.canopy_rest.clone();
+    for ((tree, canopy), scale) in world.canopies.iter() {
+        let entity = playback
+            .trees
+            .get(*tree as usize)
+            .and_then(|tree| children_query.get(*tree).ok())
+            .and_then(|children| children.get(*canopy as usize).copied());
+        let Some(entity) = entity else {
+            continue;
+        };
+        let Ok(transform) = transform_query.get(entity) else {
+            continue;
+        };
+        playback
+            .canopy_rest
+            .entry(entity)
+            .or_insert(transform.scale);
+        canopy_scales.insert(entity, Vec3::splat(*scale));
+    }
+    for (entity, scale) in canopy_scales {
+        if let Ok(mut transform) = transform_query.get_mut(entity) {
+            if transform.scale != scale {
+                transform.scale = scale;
+            }
+        }
+    }
+}
+
+/// Moves the replay clock on and rebuilds the world at that moment. Seeking backwards replays
+/// the bush events from the start.
+#[allow(clippy::too_many_arguments)]
+pub fn replay_playback_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    last_replay: Res<LastReplay>,
+    caterpillar_assets: Res<CaterpillarAssets>,
+    foliage_assets: Res<FoliageAssets>,
+    mut playback: ResMut<ReplayPlayback>,
+    mut transform_query: Query<&mut Transform>,
+    children_query: Query<&Children>,
+) {
+    let replay = &last_replay.0;
+    let duration = replay_duration(replay);
+    playback.time =
+        (playback.time + time.delta_seconds() * SPEEDS[playback.speed_index]).clamp(0.0, duration);
+
+    let playback_time = playback.time;
+    if playback.world.seek(replay, playback_time) {
+        sync_foliage(
+            &mut commands,
+            replay,
+            &foliage_assets,
+            &mut playback,
+            &mut transform_query,
+            &children_query,
+        );
+    }
+
+    let Some(frame_index) = playback.world.applied_frames.checked_sub(1) else {
+        return;
+    };
+    let frame = &replay.frames[frame_index];
+    let next_frame = replay.frames.get(frame_index + 1);
+    let t = next_frame.map_or(0.0, |next_frame| {
+        ((playback.time - frame.time) / (next_frame.time - frame.time)).clamp(0.0, 1.0)
+    });
+
+    // caterpillars missing from the frame have died, or not hatched yet.
+    let gone: Vec<u32> = playback
+        .caterpillars
+        .keys()
+        .filter(|id| !frame.caterpillars.iter().any(|c| c.id == **id))
+        .copied()
+        .collect();
+    for id in gone {
+        for entity in playback.caterpillars.remove(&id).unwrap() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+
+    for snapshot in frame.caterpillars.iter() {
+        let next_snapshot = next_frame
+            .and_then(|next_frame| next_frame.caterpillars.iter().find(|c| c.id == snapshot.id));
+        let palette = playback
+            .world
+            .caterpillars
+            .get(&snapshot.id)
+            .map_or(0, |caterpillar| caterpillar.palette);
+        let entities = playback.caterpillars.entry(snapshot.id).or_default();
+        while entities.len() < snapshot.segments.len() {
+            entities.push(spawn_replay_segment(
+                &mut commands,
+                &caterpillar_assets,
+                palette,
+                entities.len(),
+            ));
+        }
+        // the caterpillar lost its tail to a bird, or was shorter at the time seeked back to.
+        for entity in entities.drain(snapshot.segments.len()..) {
+            commands.entity(entity).despawn_recursive();
+        }
+        for (i, segment) in snapshot.segments.iter().enumerate() {
+            let next_segment = next_snapshot
+                .and_then(|next_snapshot| next_snapshot.segments.get(i))
+                .unwrap_or(segment);
+            let transform = segment_transform(segment, next_segment, t);
+            set_transform(&mut commands, &mut transform_query, entities[i], transform);
+        }
+    }
+}
+
+pub fn update_replay_ui_system(
+    last_replay: Res<LastReplay>,
+    playback: Res<ReplayPlayback>,
+    mut text_query: Query<&mut Text, With<ReplayTextUi>>,
+    mut fill_query: Query<&mut Style, With<ScrubBarFill>>,
+) {
+    let duration = replay_duration(&last_replay.0);
+    for mut text in text_query.iter_mut() {
+        text.sections[0].value = format!(
+            "Replay {:.1}s / {:.1}s at {}x, {} caterpillars\nSpace play/pause, arrows seek, [ ] speed, Esc menu",
+            playback.time,
+            duration,
+            SPEEDS[playback.speed_index],
+            playback.world.caterpillars.len(),
+        );
+    }
+    let fraction = if duration > 0.0 {
+        playback.time / duration
+    } else {
+        0.0
+    };
+    for mut style in fill_query.iter_mut() {
+        style.size.width = Val::Percent(fraction * 100.0);
+    }
+}
+
+#[cfg(test)]
+fn test_replay() -> Replay {
+    Replay {
+        config: crate::dynamic_config::test_config(),
+        trees: vec![[20.0, 0.0, 30.0]],
+        frames: vec![
+            ReplayFrame {
+                time: 0.0,
+                caterpillars: vec![CaterpillarSnapshot {
+                    id: 0,
+                    segments: vec![[0, 0, 0, 0], [0, 0, -10, 0]],
+                }],
+                events: vec![
+                    ReplayEvent::Spawn {
+                        id: 0,
+                        name: "Test".to_string(),
+                        palette: 2,
+                    },
+                    ReplayEvent::FoodSpawn {
+                        id: 1,
+                        position: [10.0, 0.0, -5.5],
+                        kind: 1,
+                    },
+                ],
+            },
+            ReplayFrame {
+                time: 1.0,
+                caterpillars: vec![CaterpillarSnapshot {
+                    id: 0,
+                    segments: vec![[0, 0, 50, 157], [0, 0, 40, 157]],
+                }],
+                events: vec![
+                    ReplayEvent::FoodResized { id: 1, scale: 0.5 },
+                    ReplayEvent::CanopyResized {
+                        tree: 0,
+                        canopy: 3,
+                        scale: 0.8,
+                    },
+                ],
+            },
+            ReplayFrame {
+                time: 2.0,
+                caterpillars: vec![],
+                events: vec![
+                    ReplayEvent::FoodEaten { id: 1 },
+                    ReplayEvent::Despawn { id: 0 },
+                ],
+            },
+        ],
+    }
+}
+
+#[test]
+#[cfg(test)]
+fn replay_round_trip_test_1() {
+    let replay = test_replay();
+
+    let loaded = decode_replay(&encode_replay(&replay).unwrap()).unwrap();
+
+    // Check resulting changes
+    assert_eq!(loaded.trees, replay.trees);
+    assert_eq!(loaded.frames, replay.frames);
+}
+
+#[test]
+#[cfg(test)]
+fn replay_round_trip_test_2() {
+    let replay = test_replay();
+
+    // replays saved before they were compressed are plain JSON.
+    let loaded = decode_replay(&serde_json::to_vec(&replay).unwrap()).unwrap();
+
+    // Check resulting changes
+    assert_eq!(loaded.trees, replay.trees);
+    assert_eq!(loaded.frames, replay.frames);
+}
+
+#[test]
+#[cfg(test)]
+fn seek_test_1() {
+    let replay = test_replay();
+    let mut world = ReplayWorld::default();
+
+    let changed = world.seek(&replay, 0.5);
+
+    // Check resulting changes
+    assert!(changed);
+    assert_eq!(world.applied_frames, 1);
+    assert_eq!(world.caterpillars[&0].palette, 2);
+    assert_eq!(world.food[&1].scale, 1.0);
+    assert!(world.canopies.is_empty());
+}
+
+#[test]
+#[cfg(test)]
+fn seek_test_2() {
+    let replay = test_replay();
+    let mut world = ReplayWorld::default();
+    world.seek(&replay, 0.5);
+
+    // Check resulting changes
+    assert!(!world.seek(&replay, 0.9));
+    assert!(world.seek(&replay, 1.0));
+    assert_eq!(world.food[&1].scale, 0.5);
+    assert_eq!(world.canopies[&(0, 3)], 0.8);
+    assert!(world.seek(&replay, 5.0));
+    assert_eq!(world.applied_frames, 3);
+    assert!(world.caterpillars.is_empty());
+    assert!(world.food.is_empty());
+}
+
+#[test]
+#[cfg(test)]
+fn seek_test_3() {
+    let replay = test_replay();
+    let mut world = ReplayWorld::default();
+    world.seek(&replay, 5.0);
+
+    let changed = world.seek(&replay, 0.0);
+
+    // Check resulting changes
+    assert!(changed);
+    assert_eq!(world.applied_frames, 1);
+    assert!(world.caterpillars.contains_key(&0));
+    assert_eq!(world.food[&1].scale, 1.0);
+    assert!(world.canopies.is_empty());
+}
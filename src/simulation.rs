@@ -0,0 +1,129 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_tweening::Animator;
+
+use crate::AppState;
+
+/// Simulation rates that can be cycled through.
+pub const SPEEDS: [f32; 5] = [0.0, 0.25, 1.0, 4.0, 16.0];
+
+/// Index into `SPEEDS` of normal speed.
+pub const NORMAL_SPEED_INDEX: usize = 2;
+
+/// Simulated time covered by a single step while stopped.
+const STEP_SECONDS: f32 = 1.0 / 60.0;
+
+/// Scaled clock for everything that happens in the world. Systems that move or grow things
+/// should read this rather than `Time` so they all respect the speed controls.
+#[derive(Resource)]
+pub struct SimulationTime {
+    speed_index: usize,
+    step_requested: bool,
+    delta: Duration,
+    elapsed: Duration,
+    rate: f32,
+}
+
+impl Default for SimulationTime {
+    fn default() -> Self {
+        SimulationTime {
+            speed_index: NORMAL_SPEED_INDEX,
+            step_requested: false,
+            delta: Duration::ZERO,
+            elapsed: Duration::ZERO,
+            rate: 1.0,
+        }
+    }
+}
+
+impl SimulationTime {
+    /// The selected speed multiplier.
+    pub fn speed(&self) -> f32 {
+        SPEEDS[self.speed_index]
+    }
+
+    /// Simulated time since the last frame.
+    pub fn delta(&self) -> Duration {
+        self.delta
+    }
+
+    pub fn delta_seconds(&self) -> f32 {
+        self.delta.as_secs_f32()
+    }
+
+    /// Simulated time since the level started.
+    pub fn elapsed_seconds(&self) -> f32 {
+        self.elapsed.as_secs_f32()
+    }
+
+    /// How much faster than real time the simulation moved this frame.
+    pub fn rate(&self) -> f32 {
+        self.rate
+    }
+}
+
+/// Advances the simulation clock. Nothing moves unless the level is playing.
+pub fn update_simulation_time_system(
+    time: Res<Time>,
+    app_state: Res<State<AppState>>,
+    mut simulation_time: ResMut<SimulationTime>,
+) {
+    let mut delta = Duration::ZERO;
+    if *app_state.current() == AppState::Playing {
+        if simulation_time.speed_index == 0 {
+            if simulation_time.step_requested {
+                delta = Duration::from_secs_f32(STEP_SECONDS);
+            }
+        } else {
+            delta = time.delta().mul_f32(simulation_time.speed());
+        }
+    }
+    simulation_time.step_requested = false;
+
+    simulation_time.delta = delta;
+    simulation_time.elapsed += delta;
+    simulation_time.rate = if time.delta_seconds() > 0.0 {
+        delta.as_secs_f32() / time.delta_seconds()
+    } else {
+        0.0
+    };
+}
+
+/// `[` and `]` change speed, Space stops and restarts, `.` steps forward while stopped.
+pub fn time_controls_system(
+    keys: Res<Input<KeyCode>>,
+    mut simulation_time: ResMut<SimulationTime>,
+    mut resume_index: Local<Option<usize>>,
+) {
+    if keys.just_pressed(KeyCode::RBracket) && simulation_time.speed_index < SPEEDS.len() - 1 {
+        simulation_time.speed_index += 1;
+    }
+    if keys.just_pressed(KeyCode::LBracket) && simulation_time.speed_index > 0 {
+        simulation_time.speed_index -= 1;
+    }
+    if keys.just_pressed(KeyCode::Space) {
+        if simulation_time.speed_index == 0 {
+            simulation_time.speed_index = resume_index.take().unwrap_or(NORMAL_SPEED_INDEX);
+        } else {
+            *resume_index = Some(simulation_time.speed_index);
+            simulation_time.speed_index = 0;
+        }
+    }
+    if keys.just_pressed(KeyCode::Period) && simulation_time.speed_index == 0 {
+        simulation_time.step_requested = true;
+    }
+}
+
+/// Keeps tweened animations in step with the simulation clock.
+pub fn sync_animation_speed_system(
+    simulation_time: Res<SimulationTime>,
+    mut animator_query: Query<&mut Animator<Transform>>,
+) {
+    let rate = simulation_time.rate();
+    for mut animator in animator_query.iter_mut() {
+        if animator.speed() != rate {
+            animator.set_speed(rate);
+        }
+    }
+}
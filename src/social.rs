@@ -0,0 +1,321 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::prelude::*;
+
+use crate::{
+    caterpillar::{self, CaterpillarHead, CaterpillarPart, EAT_REACH},
+    climbing::Climbing,
+    collision,
+    day_night::Sleeping,
+    dynamic_config::DynamicConfig,
+    foliage::Food,
+    predator::Fleeing,
+    random,
+    simulation::SimulationTime,
+    thoughts::{self, Situation},
+    toast::ToastEvent,
+};
+
+/// A caterpillar in a procession, following the tail of the one in front.
+#[derive(Component)]
+pub struct Following {
+    pub leader: Entity,
+    /// Fraction of its speed the caterpillar moves at, so it doesn't run into the tail it follows.
+    pub pace: f32,
+}
+
+/// Keeps a caterpillar out of fights for a while after one.
+#[derive(Component)]
+pub struct ContestCooldown(pub f32);
+
+/// Position of the last segment of a caterpillar, or its head if it has no body.
+fn tail_position(
+    transform: &Transform,
+    head: &CaterpillarHead,
+    part_query: &Query<(&CaterpillarPart, &Transform)>,
+) -> Vec3 {
+    let mut position = transform.translation;
+    let mut caterpillar_part = head.next;
+    while let Some(part_entity) = caterpillar_part {
+        let Ok((part, part_transform)) = part_query.get(part_entity) else {
+            break;
+        };
+        position = part_transform.translation;
+        caterpillar_part = part.next;
+    }
+    position
+}
+
+/// Whether following `leader` would eventually lead back to `follower`.
+fn leads_to(leaders: &HashMap<Entity, Entity>, leader: Entity, follower: Entity) -> bool {
+    let mut current = leader;
+    // a procession can't be longer than the number of caterpillars in it.
+    for _ in 0..=leaders.len() {
+        if current == follower {
+            return true;
+        }
+        match leaders.get(&current) {
+            Some(next) => current = *next,
+            None => return false,
+        }
+    }
+    true
+}
+
+/// Lines caterpillars up head to tail. Wanderers that come across a tail nobody is following
+/// fall in behind it, and followers steer after the tail in front until it gets away from them
+/// or they lose interest.
+#[allow(clippy::type_complexity)]
+pub fn procession_system(
+    mut commands: Commands,
+    simulation_time: Res<SimulationTime>,
+    config: Res<DynamicConfig>,
+    mut head_query: Query<
+        (
+            Entity,
+            &Transform,
+            &mut CaterpillarHead,
+            Option<&mut Following>,
+        ),
+        (Without<Climbing>, Without<Sleeping>, Without<Fleeing>),
+    >,
+    part_query: Query<(&CaterpillarPart, &Transform)>,
+) {
+    let social = &config.social;
+    if !social.procession {
+        return;
+    }
+
+    let tails: HashMap<Entity, Vec3> = head_query
+        .iter()
+        .map(|(entity, transform, caterpillar, _)| {
+            (entity, tail_position(transform, caterpillar, &part_query))
+        })
+        .collect();
+    let mut leaders: HashMap<Entity, Entity> = head_query
+        .iter()
+        .filter_map(|(entity, _, _, following)| {
+            following.map(|following| (entity, following.leader))
+        })
+        .collect();
+    let mut followed: HashSet<Entity> = leaders.values().copied().collect();
+    let leave_chance = social.leave_chance * simulation_time.delta_seconds();
+
+    for (entity, transform, mut caterpillar, following) in head_query.iter_mut() {
+        if caterpillar.manually_controlled {
+            continue;
+        }
+
+        if let Some(mut following) = following {
+            // the leader may have climbed a tree, dozed off, run away or been eaten.
+            let tail = tails
+                .get(&following.leader)
+                .copied()
+                .filter(|tail| tail.distance(transform.translation) <= social.join_radius * 2.0);
+            match tail {
+                Some(tail) if random::range_f32(0.0, 1.0) >= leave_chance => {
+                    caterpillar.angle = caterpillar::heading_to(transform.translation, tail);
+                    caterpillar.angle_offset = 0.0;
+                    let gap = tail.distance(transform.translation) - social.follow_distance;
+                    following.pace = (gap / social.follow_distance.max(1.0)).clamp(0.0, 1.0);
+                }
+                _ => {
+                    commands.entity(entity).remove::<Following>();
+                }
+            }
+            continue;
+        }
+
+        let nearest = tails
+            .iter()
+            .filter(|(leader, _)| {
+                **leader != entity
+                    && !followed.contains(*leader)
+                    && !leads_to(&leaders, **leader, entity)
+            })
+            .map(|(leader, tail)| (*leader, tail.distance(transform.translation)))
+            .filter(|(_, distance)| *distance <= social.join_radius)
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some((leader, ..)) = nearest {
+            leaders.insert(entity, leader);
+            followed.insert(leader);
+            commands
+                .entity(entity)
+                .insert(Following { leader, pace: 1.0 });
+        }
+    }
+}
+
+/// Turns wandering caterpillars away from crowds, so they spread out over the world.
+#[allow(clippy::type_complexity)]
+pub fn crowd_system(
+    config: Res<DynamicConfig>,
+    mut head_query: Query<
+        (Entity, &Transform, &mut CaterpillarHead, Option<&Following>),
+        (Without<Climbing>, Without<Sleeping>, Without<Fleeing>),
+    >,
+) {
+    let social = &config.social;
+    if !social.avoid_crowding || social.crowd_size == 0 {
+        return;
+    }
+
+    let positions: Vec<(Entity, Vec3)> = head_query
+        .iter()
+        .map(|(entity, transform, ..)| (entity, transform.translation))
+        .collect();
+
+    for (entity, transform, mut caterpillar, following) in head_query.iter_mut() {
+        if caterpillar.manually_controlled || following.is_some() {
+            continue;
+        }
+
+        let neighbours: Vec<Vec3> = positions
+            .iter()
+            .filter(|(other, position)| {
+                *other != entity && position.distance(transform.translation) < social.crowd_radius
+            })
+            .map(|(_, position)| *position)
+            .collect();
+        if neighbours.len() < social.crowd_size {
+            continue;
+        }
+
+        let centre = neighbours.iter().sum::<Vec3>() / neighbours.len() as f32;
+        if centre.distance_squared(transform.translation) > f32::EPSILON {
+            caterpillar.angle = caterpillar::heading_to(centre, transform.translation);
+        }
+    }
+}
+
+/// Caterpillars that reach the same bush fight over it. The bigger one usually wins, and the
+/// loser is sent off hungry.
+#[allow(clippy::type_complexity)]
+pub fn competition_system(
+    mut commands: Commands,
+    simulation_time: Res<SimulationTime>,
+    config: Res<DynamicConfig>,
+    mut head_query: Query<
+        (
+            Entity,
+            &Transform,
+            &mut CaterpillarHead,
+            Option<&mut ContestCooldown>,
+        ),
+        (Without<Climbing>, Without<Sleeping>, Without<Food>),
+    >,
+    part_query: Query<&CaterpillarPart>,
+    food_query: Query<(&Transform, &Food)>,
+    mut ev_toast: EventWriter<ToastEvent>,
+) {
+    let social = &config.social;
+    if !social.competition {
+        return;
+    }
+
+    let mut contenders: Vec<(Entity, Vec3)> = Vec::new();
+    for (entity, transform, _, cooldown) in head_query.iter_mut() {
+        match cooldown {
+            Some(mut cooldown) => {
+                cooldown.0 -= simulation_time.delta_seconds();
+                if cooldown.0 <= 0.0 {
+                    commands.entity(entity).remove::<ContestCooldown>();
+                }
+            }
+            None => contenders.push((entity, transform.translation)),
+        }
+    }
+
+    let mut fought: Vec<Entity> = Vec::new();
+    for (i, (first, first_position)) in contenders.iter().enumerate() {
+        for (second, second_position) in contenders.iter().skip(i + 1) {
+            if fought.contains(first) || fought.contains(second) {
+                continue;
+            }
+            if !collision::collision_check(*first_position, *second_position, EAT_REACH * 2.0) {
+                continue;
+            }
+            let Ok([(_, _, first_head, _), (_, _, second_head, _)]) =
+                head_query.get_many([*first, *second])
+            else {
+                continue;
+            };
+
+            // a bush within reach of both that both of them would eat.
+            let bush = food_query.iter().find(|(food_transform, food)| {
+                food.amount > 0.0
+                    && collision::collision_check(
+                        *first_position,
+                        food_transform.translation,
+                        EAT_REACH,
+                    )
+                    && collision::collision_check(
+                        *second_position,
+                        food_transform.translation,
+                        EAT_REACH,
+                    )
+                    && first_head.preference(food.kind) >= config.diet.refuse_below
+                    && second_head.preference(food.kind) >= config.diet.refuse_below
+            });
+            let Some((bush_transform, food)) = bush else {
+                continue;
+            };
+
+            // size counts for a lot, but a small caterpillar can still get lucky.
+            let strength = |head: &CaterpillarHead| {
+                let length = caterpillar::caterpillar_length(head, &part_query) as f32;
+                length * random::range_f32(0.5, 1.0)
+            };
+            let (winner, loser) = if strength(first_head) >= strength(second_head) {
+                (*first, *second)
+            } else {
+                (*second, *first)
+            };
+            let Ok([(_, _, mut winner_head, _), (_, loser_transform, mut loser_head, _)]) =
+                head_query.get_many_mut([winner, loser])
+            else {
+                continue;
+            };
+
+            let food_name = config.foods[food.kind].name.to_lowercase();
+            let winner_name = winner_head.name.clone();
+            let loser_name = loser_head.name.clone();
+            thoughts::think(
+                &mut winner_head,
+                Situation::WonFight,
+                &config,
+                Some(&loser_name),
+                Some(food.kind),
+            );
+            thoughts::think(
+                &mut loser_head,
+                Situation::LostFight,
+                &config,
+                Some(&winner_name),
+                Some(food.kind),
+            );
+            loser_head.bite_cooldown = social.contest_cooldown;
+            if !loser_head.manually_controlled {
+                loser_head.angle = caterpillar::heading_to(
+                    bush_transform.translation,
+                    loser_transform.translation,
+                );
+                loser_head.angle_offset = 0.0;
+            }
+            ev_toast.send(ToastEvent {
+                message: format!(
+                    "{} won a scuffle with {} over some {}!",
+                    winner_name, loser_name, food_name
+                ),
+                expiry_tick: 4000,
+            });
+
+            for entity in [winner, loser] {
+                commands
+                    .entity(entity)
+                    .insert(ContestCooldown(social.contest_cooldown));
+                fought.push(entity);
+            }
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use bevy::{prelude::*, ui::FocusPolicy};
+
+use crate::{
+    camera::{self, PanOrbitCamera},
+    caterpillar::CaterpillarHead,
+    dynamic_config::DynamicConfig,
+};
+
+/// Height above the middle of a caterpillar's head that its bubble points at.
+const BUBBLE_HEIGHT: f32 = 5.0;
+
+/// Widest a bubble grows before its text wraps, in pixels.
+const BUBBLE_MAX_WIDTH: f32 = 220.0;
+
+const BUBBLE_COLOUR: Color = Color::rgba(1.0, 1.0, 1.0, 0.85);
+const BUBBLE_TEXT_COLOUR: Color = Color::rgb(0.1, 0.1, 0.1);
+
+/// Covers the screen and holds every speech bubble, so B can show and hide them all at once.
+#[derive(Component)]
+pub struct SpeechBubbleLayer;
+
+/// A bubble showing a caterpillar's latest thought. The text is its only child.
+#[derive(Component)]
+pub struct SpeechBubble {
+    target: Entity,
+    thought: String,
+    /// Seconds since the thought changed.
+    age: f32,
+}
+
+pub fn setup_speech_bubbles(mut commands: Commands, config: Res<DynamicConfig>) {
+    if !config.speech_bubbles.enabled {
+        return;
+    }
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                ..default()
+            },
+            focus_policy: FocusPolicy::Pass,
+            ..default()
+        })
+        .insert(SpeechBubbleLayer);
+}
+
+/// B shows and hides speech bubbles.
+pub fn toggle_speech_bubbles_system(
+    keys: Res<Input<KeyCode>>,
+    mut layer_query: Query<&mut Visibility, With<SpeechBubbleLayer>>,
+) {
+    if keys.just_pressed(KeyCode::B) {
+        for mut visibility in layer_query.iter_mut() {
+            visibility.is_visible = !visibility.is_visible;
+        }
+    }
+}
+
+/// Pops up a bubble above a caterpillar when its thought changes and fades it out after a while.
+/// Bubbles are hidden for caterpillars that are off screen or far from the camera, and removed
+/// along with their caterpillar.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn speech_bubble_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    config: Res<DynamicConfig>,
+    asset_server: Res<AssetServer>,
+    layer_query: Query<Entity, With<SpeechBubbleLayer>>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<PanOrbitCamera>>,
+    new_caterpillar_query: Query<(Entity, &CaterpillarHead), Added<CaterpillarHead>>,
+    caterpillar_query: Query<(&GlobalTransform, &CaterpillarHead)>,
+    mut bubble_query: Query<(
+        Entity,
+        &mut SpeechBubble,
+        &mut Style,
+        &mut Visibility,
+        &mut BackgroundColor,
+        &Node,
+        &Children,
+    )>,
+    mut text_query: Query<&mut Text>,
+) {
+    let Ok(layer) = layer_query.get_single() else {
+        return;
+    };
+    let bubble_config = &config.speech_bubbles;
+
+    if !new_caterpillar_query.is_empty() {
+        let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+        commands.entity(layer).with_children(|parent| {
+            for (entity, caterpillar) in new_caterpillar_query.iter() {
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            position_type: PositionType::Absolute,
+                            padding: UiRect::all(Val::Px(6.0)),
+                            ..default()
+                        },
+                        background_color: BUBBLE_COLOUR.into(),
+                        visibility: Visibility { is_visible: false },
+                        focus_policy: FocusPolicy::Pass,
+                        ..default()
+                    })
+                    .insert(SpeechBubble {
+                        target: entity,
+                        thought: caterpillar.description.clone(),
+                        // a caterpillar's first thought isn't news.
+                        age: bubble_config.duration,
+                    })
+                    .with_children(|bubble| {
+                        bubble.spawn(
+                            TextBundle::from_section(
+                                "",
+                                TextStyle {
+                                    font: font.clone(),
+                                    font_size: bubble_config.font_size,
+                                    color: BUBBLE_TEXT_COLOUR,
+                                },
+                            )
+                            .with_style(Style {
+                                max_size: Size::new(Val::Px(BUBBLE_MAX_WIDTH), Val::Undefined),
+                                ..default()
+                            }),
+                        );
+                    });
+            }
+        });
+    }
+
+    let camera = camera_query.iter().next();
+    for (bubble_entity, mut bubble, mut style, mut visibility, mut colour, node, children) in
+        bubble_query.iter_mut()
+    {
+        let Ok((transform, caterpillar)) = caterpillar_query.get(bubble.target) else {
+            commands.entity(bubble_entity).despawn_recursive();
+            continue;
+        };
+
+        if caterpillar.description != bubble.thought {
+            bubble.thought = caterpillar.description.clone();
+            bubble.age = 0.0;
+        } else {
+            bubble.age += time.delta_seconds();
+        }
+
+        // fully opaque until it is time to fade.
+        let remaining = bubble_config.duration - bubble.age;
+        let opacity = (remaining / bubble_config.fade_seconds.max(0.01)).clamp(0.0, 1.0);
+        let anchor = transform.translation() + Vec3::Y * BUBBLE_HEIGHT;
+        let screen_position = camera
+            .filter(|(_, camera_transform)| {
+                camera_transform.translation().distance(anchor) <= bubble_config.max_distance
+            })
+            .and_then(|(camera, camera_transform)| {
+                camera::world_to_ui(camera, camera_transform, anchor)
+            });
+
+        let shown = opacity > 0.0 && screen_position.is_some();
+        if visibility.is_visible != shown {
+            visibility.is_visible = shown;
+        }
+        let Some(screen_position) = screen_position.filter(|_| shown) else {
+            continue;
+        };
+
+        // the bubble sits above the head, centred on it.
+        let size = node.size();
+        style.position = UiRect {
+            left: Val::Px(screen_position.x - size.x / 2.0),
+            top: Val::Px(screen_position.y - size.y),
+            ..default()
+        };
+        colour.0.set_a(BUBBLE_COLOUR.a() * opacity);
+        for child in children.iter() {
+            if let Ok(mut text) = text_query.get_mut(*child) {
+                // text is laid out again whenever it changes, so only touch it when needed.
+                if text.sections[0].value != bubble.thought {
+                    text.sections[0].value = bubble.thought.clone();
+                }
+                if (text.sections[0].style.color.a() - opacity).abs() > 0.01 {
+                    text.sections[0].style.color.set_a(opacity);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,252 @@
+use bevy::prelude::*;
+
+use crate::{
+    caterpillar::CaterpillarHead, day_night::Sleeping, dynamic_config::DynamicConfig,
+    predator::Fleeing, random, simulation::SimulationTime, social::Following,
+};
+
+/// How long a new caterpillar holds on to its first thought, unless its situation changes.
+pub const FIRST_THOUGHT_SECONDS: f32 = 10.0;
+
+/// What a caterpillar is up to, which decides what it thinks about.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Situation {
+    Wandering,
+    Hungry,
+    JustAte,
+    NearCaterpillar,
+    Night,
+    Fleeing,
+    Following,
+    WonFight,
+    LostFight,
+}
+
+impl Situation {
+    /// Thoughts about something that happened, rather than something still going on, are held
+    /// for a full interval before the caterpillar moves on.
+    fn is_event(self) -> bool {
+        matches!(self, Situation::WonFight | Situation::LostFight)
+    }
+
+    fn thoughts(self, config: &DynamicConfig) -> &Vec<String> {
+        let thinking = &config.thinking;
+        let thoughts = match self {
+            Situation::Wandering => &config.thoughts,
+            Situation::Hungry => &thinking.hungry,
+            Situation::JustAte => &thinking.just_ate,
+            Situation::NearCaterpillar => &thinking.near_caterpillar,
+            Situation::Night => &thinking.night,
+            Situation::Fleeing => &thinking.fleeing,
+            Situation::Following => &thinking.following,
+            Situation::WonFight => &thinking.won_fight,
+            Situation::LostFight => &thinking.lost_fight,
+        };
+        if thoughts.is_empty() {
+            &config.thoughts
+        } else {
+            thoughts
+        }
+    }
+}
+
+fn food_name(config: &DynamicConfig, kind: Option<usize>) -> String {
+    kind.and_then(|kind| config.foods.get(kind))
+        .map(|food| food.name.to_lowercase())
+        .unwrap_or_else(|| "leaves".to_string())
+}
+
+/// Gives a caterpillar a new thought about its situation. `other` names the caterpillar it is
+/// thinking about, and `food` the food, which defaults to its favourite.
+pub fn think(
+    caterpillar: &mut CaterpillarHead,
+    situation: Situation,
+    config: &DynamicConfig,
+    other: Option<&str>,
+    food: Option<usize>,
+) {
+    let thoughts = situation.thoughts(config);
+    if thoughts.is_empty() {
+        return;
+    }
+
+    let food = food.or_else(|| caterpillar.favourite_food());
+    let thought = random::from_vec(thoughts)
+        .replace("{name}", &caterpillar.name)
+        .replace("{food}", &food_name(config, food))
+        .replace("{other}", other.unwrap_or("someone"));
+    caterpillar.description = thought;
+    caterpillar.situation = situation;
+    // so a crowd of caterpillars don't all change their minds at once.
+    caterpillar.thought_timer = config.thinking.interval * random::range_f32(0.75, 1.25);
+}
+
+/// Works out what each caterpillar is up to and gives it something to think about. A new thought
+/// comes when its situation changes, and every so often when it doesn't.
+#[allow(clippy::type_complexity)]
+pub fn thought_system(
+    simulation_time: Res<SimulationTime>,
+    config: Res<DynamicConfig>,
+    mut caterpillar_query: Query<(
+        Entity,
+        &Transform,
+        &mut CaterpillarHead,
+        Option<&Sleeping>,
+        Option<&Fleeing>,
+        Option<&Following>,
+    )>,
+) {
+    let thinking = &config.thinking;
+    let now = simulation_time.elapsed_seconds();
+    let caterpillars: Vec<(Entity, Vec3, String)> = caterpillar_query
+        .iter()
+        .map(|(entity, transform, caterpillar, ..)| {
+            (entity, transform.translation, caterpillar.name.clone())
+        })
+        .collect();
+
+    for (entity, transform, mut caterpillar, sleeping, fleeing, following) in
+        caterpillar_query.iter_mut()
+    {
+        caterpillar.thought_timer -= simulation_time.delta_seconds();
+        if caterpillar.situation.is_event() && caterpillar.thought_timer > 0.0 {
+            continue;
+        }
+
+        let nearest = || {
+            caterpillars
+                .iter()
+                .filter(|(other, position, _)| {
+                    *other != entity
+                        && position.distance(transform.translation) < thinking.near_radius
+                })
+                .min_by(|a, b| {
+                    a.1.distance(transform.translation)
+                        .total_cmp(&b.1.distance(transform.translation))
+                })
+                .map(|(_, _, name)| name.as_str())
+        };
+        let since_meal = now - caterpillar.last_meal;
+        let (situation, other) = if fleeing.is_some() {
+            (Situation::Fleeing, None)
+        } else if sleeping.is_some() {
+            (Situation::Night, None)
+        } else if let Some(following) = following {
+            let leader = caterpillars
+                .iter()
+                .find(|(other, ..)| *other == following.leader)
+                .map(|(_, _, name)| name.as_str());
+            (Situation::Following, leader)
+        } else if caterpillar.food_eaten > 0 && since_meal < thinking.just_ate_for {
+            (Situation::JustAte, None)
+        } else if since_meal > thinking.hungry_after {
+            (Situation::Hungry, None)
+        } else if let Some(name) = nearest() {
+            (Situation::NearCaterpillar, Some(name))
+        } else {
+            (Situation::Wandering, None)
+        };
+
+        if situation == caterpillar.situation && caterpillar.thought_timer > 0.0 {
+            continue;
+        }
+        let food = if situation == Situation::JustAte {
+            caterpillar.last_food
+        } else {
+            None
+        };
+        think(&mut caterpillar, situation, &config, other, food);
+    }
+}
+
+#[cfg(test)]
+fn test_caterpillar(diet: Vec<f32>) -> CaterpillarHead {
+    CaterpillarHead {
+        speed: 5.0,
+        next: None,
+        manually_controlled: false,
+        wander_timer: 0.0,
+        name: "Ada".to_string(),
+        description: String::new(),
+        situation: Situation::Wandering,
+        thought_timer: 0.0,
+        food_eaten: 0,
+        last_meal: 0.0,
+        last_food: None,
+        bite_cooldown: 0.0,
+        nourishment: 0.0,
+        diet,
+        distance_travelled: 0.0,
+        born: 0.0,
+        palette: 0,
+        angle: 0.0,
+        angle_offset: 0.0,
+        angle_offset_direction: crate::caterpillar::AngleOffsetDirection::Left,
+    }
+}
+
+#[test]
+#[cfg(test)]
+fn think_test_1() {
+    let mut config = crate::dynamic_config::test_config();
+    config.foods[1].name = "Flowers".to_string();
+    config.thinking.hungry = vec!["{name} wants {food} and {other} knows it.".to_string()];
+    let mut caterpillar = test_caterpillar(vec![]);
+
+    think(
+        &mut caterpillar,
+        Situation::Hungry,
+        &config,
+        Some("Bo"),
+        Some(1),
+    );
+
+    // Check resulting changes
+    assert_eq!(
+        caterpillar.description,
+        "Ada wants flowers and Bo knows it."
+    );
+    assert_eq!(caterpillar.situation, Situation::Hungry);
+}
+
+#[test]
+#[cfg(test)]
+fn think_test_2() {
+    let mut config = crate::dynamic_config::test_config();
+    config.foods[2].name = "Fruit".to_string();
+    config.thinking.hungry = vec!["{food} for {other}".to_string()];
+    let mut caterpillar = test_caterpillar(vec![0.5, 1.0, 2.0]);
+
+    think(&mut caterpillar, Situation::Hungry, &config, None, None);
+
+    // Check resulting changes
+    assert_eq!(caterpillar.description, "fruit for someone");
+}
+
+#[test]
+#[cfg(test)]
+fn think_test_3() {
+    let mut config = crate::dynamic_config::test_config();
+    config.thinking.hungry = vec!["{food}".to_string()];
+    let mut caterpillar = test_caterpillar(vec![]);
+
+    think(&mut caterpillar, Situation::Hungry, &config, None, None);
+
+    // Check resulting changes
+    assert_eq!(caterpillar.description, "leaves");
+}
+
+#[test]
+#[cfg(test)]
+fn think_test_4() {
+    let mut config = crate::dynamic_config::test_config();
+    config.thoughts = vec!["{name} is just thinking.".to_string()];
+    config.thinking.lost_fight.clear();
+    let mut caterpillar = test_caterpillar(vec![]);
+
+    think(&mut caterpillar, Situation::LostFight, &config, None, None);
+
+    // Check resulting changes
+    assert_eq!(caterpillar.description, "Ada is just thinking.");
+    assert_eq!(caterpillar.situation, Situation::LostFight);
+}
@@ -0,0 +1,161 @@
+use std::collections::VecDeque;
+
+use bevy::{
+    prelude::*,
+    render::{mesh::PrimitiveTopology, view::NoFrustumCulling},
+};
+
+use crate::{caterpillar::CaterpillarHead, dynamic_config::DynamicConfig, random};
+
+/// Height above the ground trails are drawn at, so they aren't hidden by it.
+const TRAIL_HEIGHT: f32 = 0.3;
+
+/// A fading line following a caterpillar's head.
+#[derive(Component)]
+pub struct Trail {
+    pub head: Entity,
+    pub colour: Color,
+    points: VecDeque<Vec3>,
+}
+
+#[derive(Resource)]
+pub struct TrailAssets {
+    pub material_handle: Handle<StandardMaterial>,
+    pub visible: bool,
+}
+
+pub fn setup_trails(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    config: Res<DynamicConfig>,
+) {
+    // the colour and fade come from the mesh's vertex colours.
+    let material_handle = materials.add(StandardMaterial {
+        base_color: Color::WHITE,
+        unlit: true,
+        alpha_mode: AlphaMode::Blend,
+        ..default()
+    });
+    commands.insert_resource(TrailAssets {
+        material_handle,
+        visible: config.trails.enabled,
+    });
+}
+
+fn trail_mesh(points: &VecDeque<Vec3>, colour: Color, length: usize) -> Mesh {
+    let mut positions: Vec<[f32; 3]> = points.iter().map(|point| point.to_array()).collect();
+    // a line strip needs two points to draw anything.
+    while positions.len() < 2 {
+        positions.push(positions.last().copied().unwrap_or_default());
+    }
+
+    // oldest points are at the front and fade out.
+    let offset = length.saturating_sub(positions.len());
+    let colours: Vec<[f32; 4]> = (0..positions.len())
+        .map(|i| {
+            let alpha = (offset + i + 1) as f32 / length.max(1) as f32;
+            let mut faded = colour;
+            faded.set_a(alpha);
+            faded.as_linear_rgba_f32()
+        })
+        .collect();
+
+    let mut mesh = Mesh::new(PrimitiveTopology::LineStrip);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colours);
+    mesh
+}
+
+/// Gives each new caterpillar a trail, and clears away trails of caterpillars that have gone.
+pub fn spawn_trails_system(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    config: Res<DynamicConfig>,
+    trail_assets: Res<TrailAssets>,
+    new_head_query: Query<(Entity, &Transform), Added<CaterpillarHead>>,
+    head_query: Query<&CaterpillarHead>,
+    trail_query: Query<(Entity, &Trail)>,
+) {
+    for (head_entity, transform) in new_head_query.iter() {
+        let colour = Color::hsl(random::range_f32(0.0, 360.0), 0.8, 0.6);
+        let mut points = VecDeque::new();
+        points.push_back(Vec3::new(
+            transform.translation.x,
+            TRAIL_HEIGHT,
+            transform.translation.z,
+        ));
+        let mesh = trail_mesh(&points, colour, config.trails.length);
+        commands
+            .spawn(PbrBundle {
+                mesh: meshes.add(mesh),
+                material: trail_assets.material_handle.clone(),
+                visibility: Visibility {
+                    is_visible: trail_assets.visible,
+                },
+                ..default()
+            })
+            // the bounds are only worked out when the mesh is first added, so they'd be wrong as
+            // the trail grows.
+            .insert(NoFrustumCulling)
+            .insert(Trail {
+                head: head_entity,
+                colour,
+                points,
+            });
+    }
+
+    for (trail_entity, trail) in trail_query.iter() {
+        if !head_query.contains(trail.head) {
+            commands.entity(trail_entity).despawn_recursive();
+        }
+    }
+}
+
+/// Adds a point to each trail once its caterpillar has moved far enough, dropping the oldest.
+pub fn update_trails_system(
+    mut meshes: ResMut<Assets<Mesh>>,
+    config: Res<DynamicConfig>,
+    head_query: Query<&Transform, With<CaterpillarHead>>,
+    mut trail_query: Query<(&mut Trail, &Handle<Mesh>)>,
+) {
+    let length = config.trails.length.max(2);
+    for (mut trail, mesh_handle) in trail_query.iter_mut() {
+        let Ok(head_transform) = head_query.get(trail.head) else {
+            continue;
+        };
+        let point = Vec3::new(
+            head_transform.translation.x,
+            TRAIL_HEIGHT,
+            head_transform.translation.z,
+        );
+        let moved = trail.points.back().map_or(true, |last| {
+            last.distance(point) >= config.trails.point_spacing
+        });
+        if !moved {
+            continue;
+        }
+
+        trail.points.push_back(point);
+        while trail.points.len() > length {
+            trail.points.pop_front();
+        }
+        if let Some(mesh) = meshes.get_mut(mesh_handle) {
+            *mesh = trail_mesh(&trail.points, trail.colour, length);
+        }
+    }
+}
+
+/// T shows and hides the trails.
+pub fn toggle_trails_system(
+    keys: Res<Input<KeyCode>>,
+    mut trail_assets: ResMut<TrailAssets>,
+    mut trail_query: Query<&mut Visibility, With<Trail>>,
+) {
+    if !keys.just_pressed(KeyCode::T) {
+        return;
+    }
+    trail_assets.visible = !trail_assets.visible;
+    for mut visibility in trail_query.iter_mut() {
+        visibility.is_visible = trail_assets.visible;
+    }
+}
@@ -3,7 +3,9 @@ use bevy::{
     prelude::*,
 };
 
-use crate::{camera, caterpillar::CaterpillarHead, config};
+use crate::{
+    camera, caterpillar::CaterpillarHead, config, day_night::DayNight, simulation::SimulationTime,
+};
 
 #[derive(Component)]
 pub struct TextChanges;
@@ -25,6 +27,9 @@ pub struct SelectedCaterpillar;
 #[derive(Component)]
 pub struct DebugUi;
 
+#[derive(Component)]
+pub struct SpeedUi;
+
 pub fn infotext_system(mut commands: Commands, asset_server: Res<AssetServer>) {
     let mono_font = asset_server.load("fonts/FiraMono-Regular.ttf");
     let regular_font: Handle<Font> = asset_server.load("fonts/FiraSans-Bold.ttf");
@@ -125,7 +130,7 @@ pub fn infotext_system(mut commands: Commands, asset_server: Res<AssetServer>) {
                 sections: vec![TextSection {
                     value: "".to_string(),
                     style: TextStyle {
-                        font: mono_font,
+                        font: mono_font.clone(),
                         font_size: 18.0,
                         color: Color::WHITE,
                     },
@@ -135,6 +140,33 @@ pub fn infotext_system(mut commands: Commands, asset_server: Res<AssetServer>) {
             ..default()
         })
         .insert(DebugUi);
+
+    commands
+        .spawn(TextBundle {
+            style: Style {
+                align_self: AlignSelf::FlexEnd,
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    bottom: Val::Px(5.0),
+                    left: Val::Px(15.0),
+                    ..default()
+                },
+                ..default()
+            },
+            text: Text {
+                sections: vec![TextSection {
+                    value: "".to_string(),
+                    style: TextStyle {
+                        font: mono_font,
+                        font_size: 24.0,
+                        color: Color::WHITE,
+                    },
+                }],
+                alignment: Default::default(),
+            },
+            ..default()
+        })
+        .insert(SpeedUi);
 }
 
 pub fn change_text_system(
@@ -179,6 +211,25 @@ pub fn update_flavour_text_system(
     }
 }
 
+pub fn update_speed_ui_system(
+    simulation_time: Res<SimulationTime>,
+    day_night: Res<DayNight>,
+    mut query: Query<&mut Text, With<SpeedUi>>,
+) {
+    for mut text in query.iter_mut() {
+        let speed = simulation_time.speed();
+        let mut value = if speed == 0.0 {
+            "Stopped ([ ] speed, space resume, . step)".to_string()
+        } else {
+            format!("Speed {}x ([ ] speed, space stop)", speed)
+        };
+        if day_night.enabled {
+            value = format!("{} - {}", day_night.clock(), value);
+        }
+        text.sections[0].value = value;
+    }
+}
+
 pub fn update_debug_ui_system(
     windows: Res<Windows>,
     mut query: Query<&mut Text, With<DebugUi>>,
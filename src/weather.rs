@@ -0,0 +1,261 @@
+use bevy::prelude::*;
+
+use crate::{
+    camera::PanOrbitCamera,
+    dynamic_config::{DynamicConfig, SeasonConfig},
+    foliage::{Canopy, FoliageAssets},
+    pick_events::Ground,
+    random,
+    simulation::SimulationTime,
+    toast::ToastEvent,
+};
+
+/// Rain only falls around where the camera is looking, there's no need to fill the whole world.
+const RAIN_RADIUS: f32 = 150.0;
+const RAIN_HEIGHT: f32 = 120.0;
+const RAIN_FALL_SPEED: f32 = 150.0;
+
+/// How much stronger the wind blows while it rains.
+const STORM_WIND: f32 = 1.5;
+
+#[derive(Component)]
+pub struct Raindrop;
+
+/// The weather and season right now.
+#[derive(Resource)]
+pub struct Weather {
+    pub enabled: bool,
+    pub raining: bool,
+    /// Index into the configured seasons.
+    pub season: usize,
+    change_timer: Timer,
+    season_timer: Timer,
+    /// Set when the season's colours still need to be painted onto the world.
+    season_changed: bool,
+}
+
+impl Weather {
+    pub fn current_season<'a>(&self, config: &'a DynamicConfig) -> Option<&'a SeasonConfig> {
+        if self.enabled {
+            config.weather.seasons.get(self.season)
+        } else {
+            None
+        }
+    }
+
+    /// How much the weather slows caterpillars down, 1 being not at all.
+    pub fn speed_multiplier(&self, config: &DynamicConfig) -> f32 {
+        if self.raining {
+            config.weather.rain_speed_multiplier
+        } else {
+            1.0
+        }
+    }
+
+    /// Bushes spawned each second. The season sets the rate, and rain makes them grow faster.
+    pub fn bush_spawn_rate(&self, config: &DynamicConfig) -> i32 {
+        let rate = self
+            .current_season(config)
+            .map_or(config.bush_spawn_rate, |season| season.bush_spawn_rate);
+        if self.raining {
+            (rate as f32 * config.weather.rain_growth_multiplier).round() as i32
+        } else {
+            rate
+        }
+    }
+}
+
+fn colour(rgb: [f32; 3]) -> Color {
+    Color::rgb(rgb[0], rgb[1], rgb[2])
+}
+
+pub fn setup_weather(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    config: Res<DynamicConfig>,
+) {
+    let weather_config = &config.weather;
+    commands.insert_resource(Weather {
+        enabled: weather_config.enabled,
+        raining: false,
+        season: 0,
+        change_timer: Timer::from_seconds(
+            weather_config.change_interval.max(1.0),
+            TimerMode::Repeating,
+        ),
+        season_timer: Timer::from_seconds(
+            weather_config.season_length.max(1.0),
+            TimerMode::Repeating,
+        ),
+        season_changed: true,
+    });
+
+    if !weather_config.enabled {
+        return;
+    }
+
+    let raindrop_mesh_handle = meshes.add(Mesh::from(shape::Box::new(0.15, 3.0, 0.15)));
+    let raindrop_material_handle = materials.add(StandardMaterial {
+        base_color: Color::rgba(0.7, 0.8, 1.0, 0.5),
+        unlit: true,
+        alpha_mode: AlphaMode::Blend,
+        ..default()
+    });
+    for _ in 0..weather_config.rain_drops {
+        let mut translation = random::vec3(RAIN_RADIUS);
+        translation.y = random::range_f32(0.0, RAIN_HEIGHT);
+        commands
+            .spawn(PbrBundle {
+                mesh: raindrop_mesh_handle.clone(),
+                material: raindrop_material_handle.clone(),
+                transform: Transform::from_translation(translation),
+                visibility: Visibility { is_visible: false },
+                ..default()
+            })
+            .insert(Raindrop);
+    }
+}
+
+/// Decides when it rains and moves the seasons on, repainting the ground and foliage as they
+/// change.
+pub fn weather_system(
+    simulation_time: Res<SimulationTime>,
+    config: Res<DynamicConfig>,
+    mut weather: ResMut<Weather>,
+    foliage_assets: Res<FoliageAssets>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    ground_query: Query<&Handle<StandardMaterial>, With<Ground>>,
+    mut ev_toast: EventWriter<ToastEvent>,
+) {
+    if !weather.enabled {
+        return;
+    }
+    let weather_config = &config.weather;
+
+    if weather
+        .change_timer
+        .tick(simulation_time.delta())
+        .just_finished()
+    {
+        let raining = random::range_f32(0.0, 1.0) < weather_config.rain_chance;
+        if raining != weather.raining {
+            weather.raining = raining;
+            ev_toast.send(ToastEvent {
+                message: if raining {
+                    "It starts to rain.".to_string()
+                } else {
+                    "The rain stops.".to_string()
+                },
+                expiry_tick: 3000,
+            });
+        }
+    }
+
+    let season_count = weather_config.seasons.len();
+    if weather_config.season_length > 0.0 && season_count > 1 {
+        let seasons_passed = weather
+            .season_timer
+            .tick(simulation_time.delta())
+            .times_finished_this_tick() as usize;
+        if seasons_passed > 0 {
+            weather.season = (weather.season + seasons_passed) % season_count;
+            weather.season_changed = true;
+            ev_toast.send(ToastEvent {
+                message: format!(
+                    "{} has arrived.",
+                    weather_config.seasons[weather.season].name
+                ),
+                expiry_tick: 5000,
+            });
+        }
+    }
+
+    if !weather.season_changed {
+        return;
+    }
+    weather.season_changed = false;
+    let Some(season) = weather.current_season(&config) else {
+        return;
+    };
+
+    for material_handle in ground_query.iter() {
+        if let Some(material) = materials.get_mut(material_handle) {
+            material.base_color = colour(season.ground_colour);
+        }
+    }
+    if let Some(material) = materials.get_mut(&foliage_assets.tree_green_material_handle) {
+        material.base_color = colour(season.tree_colour);
+    }
+    let strength = season.bush_tint_strength.clamp(0.0, 1.0);
+    for (food_type, (_, material_handle)) in config.foods.iter().zip(&foliage_assets.food_handles) {
+        if let Some(material) = materials.get_mut(material_handle) {
+            let tinted = Vec3::from(food_type.colour).lerp(Vec3::from(season.bush_tint), strength);
+            material.base_color = colour(tinted.into());
+        }
+    }
+}
+
+/// Drops rain around the camera's focus while it is raining.
+pub fn rain_system(
+    simulation_time: Res<SimulationTime>,
+    weather: Res<Weather>,
+    camera_query: Query<&PanOrbitCamera>,
+    mut raindrop_query: Query<(&mut Transform, &mut Visibility), With<Raindrop>>,
+) {
+    let focus = camera_query
+        .iter()
+        .next()
+        .map_or(Vec3::ZERO, |camera| camera.focus);
+    let fall = RAIN_FALL_SPEED * simulation_time.delta_seconds();
+
+    for (mut transform, mut visibility) in raindrop_query.iter_mut() {
+        if visibility.is_visible != weather.raining {
+            visibility.is_visible = weather.raining;
+        }
+        if !weather.raining {
+            continue;
+        }
+
+        transform.translation.y -= fall;
+        if transform.translation.y < 0.0 {
+            let offset = random::vec3(RAIN_RADIUS);
+            transform.translation = Vec3::new(
+                focus.x + offset.x,
+                transform.translation.y.rem_euclid(RAIN_HEIGHT),
+                focus.z + offset.z,
+            );
+        }
+    }
+}
+
+/// Sways tree canopies back and forth in the wind.
+pub fn wind_system(
+    simulation_time: Res<SimulationTime>,
+    config: Res<DynamicConfig>,
+    weather: Res<Weather>,
+    mut canopy_query: Query<(&mut Transform, &Canopy)>,
+) {
+    if !weather.enabled || simulation_time.delta_seconds() == 0.0 {
+        return;
+    }
+
+    let weather_config = &config.weather;
+    let time = simulation_time.elapsed_seconds();
+    // the wind gusts, rising and falling slowly on top of the sway.
+    let gust = 0.75 + 0.25 * (time * 0.3).sin();
+    let strength =
+        weather_config.wind_strength * gust * if weather.raining { STORM_WIND } else { 1.0 };
+    let direction = Vec3::new(
+        weather_config.wind_direction.to_radians().cos(),
+        0.0,
+        weather_config.wind_direction.to_radians().sin(),
+    );
+
+    for (mut transform, canopy) in canopy_query.iter_mut() {
+        let sway = (time * weather_config.wind_speed + canopy.phase).sin();
+        // the higher up the tree, the further it moves.
+        let height_factor = (canopy.rest.y / config.tree_height + 0.5).max(0.0);
+        transform.translation = canopy.rest + direction * strength * sway * height_factor;
+    }
+}